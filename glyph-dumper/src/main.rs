@@ -19,6 +19,69 @@ struct Opts {
     /// A comma-separated list of which glyphs to dump images for (default: all glyphs).
     #[clap(short, long, use_delimiter = true)]
     glyphs: Option<Vec<u16>>,
+    /// Instead of one PNG per glyph, pack every glyph into a single "atlas" image (`atlas.png`),
+    /// with a sidecar `atlas.json` mapping each glyph id to its `(x, y, w, h)` rectangle within
+    /// it. Much faster to eyeball an entire font at once. Packs each glyph's own tight bounding
+    /// box by default, which does *not* keep baselines aligned across the sheet; pass
+    /// `--atlas-baseline-align` if you need that instead.
+    #[clap(long)]
+    atlas: bool,
+    /// atlas mode only: wrap to a new row once it would exceed this pixel width.
+    #[clap(long, default_value = "2048")]
+    atlas_width: u32,
+    /// atlas mode only: pixels of padding between packed glyphs, so neighboring glyphs' ink
+    /// (and any gamma/preblend fringing) can't bleed into each other when cropped individually.
+    #[clap(long, default_value = "1")]
+    atlas_padding: u32,
+    /// atlas mode only: place every glyph at the font's common height (as the per-file PNGs do)
+    /// instead of its own tight bounding box, so glyphs laid side by side in the sheet share a
+    /// baseline — at the cost of the tighter, variable-row-height packing `--atlas` gets by
+    /// default (see `dump_glyph_atlas`).
+    #[clap(long)]
+    atlas_baseline_align: bool,
+    /// Instead of one PNG per glyph, quantize coverage into `2^bpp` levels and write one packed
+    /// binary blob per glyph (`glyph-{:04X}.bin`): a little-endian `{width: u16, height: u16,
+    /// advance: u16}` header, followed by `height` rows of `ceil(width * bpp / 8)` bytes each,
+    /// packed MSB-first — the layout embedded displays expect for ROM-able glyph tables. One of
+    /// 1, 2, 4, or 8. Not compatible with `--atlas`.
+    #[clap(long, possible_values = &["1", "2", "4", "8"])]
+    bpp: Option<u8>,
+    /// Gamma for gamma-correcting glyph coverage into alpha (alpha = coverage^(1/gamma)),
+    /// modeled on WebRender's gamma LUT. 1.0 is linear (no correction, the previous behavior);
+    /// the default boosts thin strokes that otherwise look washed out at small sizes.
+    #[clap(long, default_value = "2.2")]
+    gamma: f32,
+    /// Also fold the target text color's luminance into the gamma correction ("preblending" in
+    /// WebRender's terminology), which boosts contrast near glyph edges a bit further.
+    #[clap(long)]
+    preblend: bool,
+    /// preblend mode only: the "r,g,b" (each in 0.0..=1.0) foreground color text will be drawn in.
+    #[clap(long, default_value = "0.0,0.0,0.0")]
+    text_color: String,
+    /// Rasterize each glyph at 3x horizontal resolution and render it as an RGB image using
+    /// standard horizontal-RGB LCD subpixel layout (the three horizontal sub-samples under each
+    /// output pixel become its R, G, B channels), which resolves much finer detail than a
+    /// single-channel coverage mask — useful for verifying fine Indic conjuncts by eye. A 5-tap
+    /// FIR filter (FreeType/WebRender's default `[0x08, 0x4D, 0x56, 0x4D, 0x08]/256`) is applied
+    /// to the supersampled coverage first, to suppress color fringing. Mutually exclusive with
+    /// `--atlas`.
+    #[clap(long)]
+    subpixel: bool,
+    /// Instead of dumping individual glyph images, render this ordered comma-separated list of
+    /// hex glyph ids (e.g. "0044,00D7,0045") side-by-side into one `layout.png`, using the font's
+    /// real horizontal advances and pair kerning rather than equal-width independent cells. Lets
+    /// you paste a run of glyph ids observed in a PDF and see what the laid-out word actually
+    /// looks like — the disambiguation step needed when authoring `replacement_text` entries in
+    /// the TOML mapping.
+    #[clap(long)]
+    layout: Option<String>,
+    /// Instead of rasterizing, export each glyph as a vector `.svg` `<path>` built directly from
+    /// its outline curves, sharing one viewBox derived from the global bounding box (so glyphs
+    /// stay comparably sized/aligned, as in the bitmap modes). Scales losslessly, which matters
+    /// for inspecting fine detail in complex conjunct shapes that a 30px bitmap loses. (rusttype
+    /// can't do this — it has no cubic-curve support — which is why this is `ab_glyph`-only.)
+    #[clap(long)]
+    svg: bool,
 }
 
 use anyhow::{Context, Result};
@@ -28,10 +91,103 @@ fn main() -> Result<()> {
     println!("Opening file {}", opts.font_file.display());
     let font_file_contents = std::fs::read(&opts.font_file)?;
     let output_dir = Path::new(&opts.output_dir).join(opts.font_file.file_name().unwrap());
-    dump_glyphs(&font_file_contents, output_dir, opts.size, opts.glyphs)?;
+    let text_color = {
+        let parts: Vec<f64> = opts
+            .text_color
+            .split(',')
+            .map(|s| s.trim().parse().expect("--text-color must be \"r,g,b\""))
+            .collect();
+        assert_eq!(parts.len(), 3, "--text-color must be \"r,g,b\"");
+        (parts[0], parts[1], parts[2])
+    };
+
+    if font_file_contents.starts_with(&[0x36, 0x04])
+        || font_file_contents.starts_with(&[0x72, 0xB5, 0x4A, 0x86])
+    {
+        std::fs::create_dir_all(output_dir.clone())?;
+        return dump_psf_glyphs(&font_file_contents, &output_dir);
+    }
+
+    if opts.svg {
+        std::fs::create_dir_all(output_dir.clone())?;
+        return dump_glyphs_svg(&font_file_contents, opts.glyphs, opts.size, &output_dir);
+    }
+
+    if let Some(spec) = &opts.layout {
+        let glyph_ids: Vec<u16> = spec
+            .split(',')
+            .map(|s| u16::from_str_radix(s.trim(), 16))
+            .collect::<std::result::Result<_, _>>()
+            .with_context(|| format!("--layout must be a comma-separated list of hex glyph ids, got {:?}", spec))?;
+        std::fs::create_dir_all(output_dir.clone())?;
+        return dump_layout(&font_file_contents, &glyph_ids, opts.size, &output_dir, gamma_lut(opts.gamma));
+    }
+
+    dump_glyphs(
+        &font_file_contents,
+        output_dir,
+        opts.size,
+        opts.glyphs,
+        opts.atlas,
+        opts.atlas_width,
+        opts.atlas_padding,
+        opts.atlas_baseline_align,
+        opts.bpp,
+        opts.gamma,
+        opts.preblend,
+        text_color,
+        opts.subpixel,
+    )?;
     Ok(())
 }
 
+/// The 5-tap FIR filter FreeType/WebRender use by default to suppress color fringing when
+/// collapsing LCD-subpixel-supersampled coverage down to output pixels (see `dump_glyphs_subpixel`).
+const LCD_FIR_FILTER: [i32; 5] = [0x08, 0x4D, 0x56, 0x4D, 0x08];
+
+/// A glyph's rectangle within an atlas image (see `dump_glyphs`' `atlas` mode), in pixels.
+#[derive(serde_derive::Serialize)]
+struct AtlasRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+/// Per-glyph typographic metrics, written alongside the images as `metrics.json` (keyed by glyph
+/// id as a 4-digit hex string, matching the other sidecar/filename conventions), mirroring the
+/// fields of the Trezor firmware's `Glyph` struct (`width`, `height`, `adv`, `bearing_x`,
+/// `bearing_y`) so downstream mapping tools can reconstruct proper text layout.
+#[derive(serde_derive::Serialize)]
+struct GlyphMetrics {
+    width: u32,
+    height: u32,
+    adv: f32,
+    bearing_x: f32,
+    bearing_y: f32,
+}
+
+/// Precompute `table[i] = round(255 * (i/255)^(1/gamma))`, modeled on WebRender's gamma LUT, so
+/// that converting an `ab_glyph` coverage value into a PNG alpha byte is a single table lookup.
+/// `gamma = 1.0` gives back the identity table (the previous, linear behavior).
+fn gamma_lut(gamma: f32) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        *entry = (255.0 * (i as f32 / 255.0).powf(1.0 / gamma)).round() as u8;
+    }
+    table
+}
+
+/// preblend mode: further boost a gamma-corrected alpha value by the luminance of the `(r, g, b)`
+/// foreground text color, the way WebRender's preblending folds the destination text color into
+/// the coverage-to-alpha conversion so edges keep more contrast against it.
+fn preblend_alpha(alpha: u8, text_color: (f64, f64, f64)) -> u8 {
+    let (r, g, b) = text_color;
+    let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+    let boosted = alpha as f64 + (255.0 - alpha as f64) * (1.0 - luminance) * 0.3;
+    boosted.round().clamp(0.0, 255.0) as u8
+}
+
 /// Parses `font_file_contents` as a font, and dumps its glyphs into `output_dir`.
 /// All the glyph images will be of height approximately `size` pixels.
 ///
@@ -117,8 +273,17 @@ fn dump_glyphs(
     output_dir: PathBuf,
     size: f32,
     glyph_ids: Option<Vec<u16>>,
+    atlas: bool,
+    atlas_width: u32,
+    atlas_padding: u32,
+    atlas_baseline_align: bool,
+    bpp: Option<u8>,
+    gamma: f32,
+    preblend: bool,
+    text_color: (f64, f64, f64),
+    subpixel: bool,
 ) -> Result<()> {
-    use ab_glyph::{Font, FontRef, GlyphId, Point};
+    use ab_glyph::{Font, FontRef, GlyphId, Point, ScaleFont};
     use image::{DynamicImage, Rgba};
 
     let font =
@@ -129,11 +294,26 @@ fn dump_glyphs(
     // Find out the idiomatic Rust way of doing this conversion.
     let glyph_ids = glyph_ids.unwrap_or((0..(font.glyph_count() as u16)).collect());
 
+    if subpixel {
+        std::fs::create_dir_all(output_dir.clone())?;
+        return dump_glyphs_subpixel(&font, &glyph_ids, size, &output_dir, gamma_lut(gamma));
+    }
+
+    let scaled_font = font.as_scaled(size);
+
     // First pass: outline the glyphs, and find the global bounding box.
     let mut x_min = i32::MAX; // empty min = infinity.
     let mut y_min = i32::MAX;
     let mut y_max = i32::MIN; // empty max = -infinity.
     let mut glyphs = Vec::new();
+    // Glyphs whose outline is present but degenerate (zero width or height — e.g. space, or a
+    // combining mark with no ink of its own): excluded from `glyphs`/the bounding box, but still
+    // worth a metrics entry and a placeholder image (see the end of this function), so each of
+    // them still gets an output entry. (Glyph ids with no outline at all, the `else` branch
+    // below, get no output entry.)
+    let mut empty_ink_glyph_ids = Vec::new();
+    let mut metrics: std::collections::HashMap<String, GlyphMetrics> =
+        std::collections::HashMap::new();
     for glyph_id in &glyph_ids {
         let glyph = GlyphId(*glyph_id).with_scale_and_position(size, Point { x: 0.0, y: 0.0 });
         // `outline_glyph` can return None when bounds are invalid for whatever reason.
@@ -144,9 +324,35 @@ fn dump_glyphs(
                 glyph_id,
                 glyph.px_bounds()
             );
-            x_min = std::cmp::min(x_min, glyph.px_bounds().min.x as i32);
-            y_min = std::cmp::min(y_min, glyph.px_bounds().min.y as i32);
-            y_max = std::cmp::max(y_max, glyph.px_bounds().max.y as i32);
+            let bounds = glyph.px_bounds();
+            if bounds.max.x <= bounds.min.x || bounds.max.y <= bounds.min.y {
+                println!("Glyph {:04X} has no ink (degenerate bounds {:?}).", glyph_id, bounds);
+                metrics.insert(
+                    format!("{:04X}", glyph_id),
+                    GlyphMetrics {
+                        width: 0,
+                        height: 0,
+                        adv: scaled_font.h_advance(GlyphId(*glyph_id)),
+                        bearing_x: 0.0,
+                        bearing_y: 0.0,
+                    },
+                );
+                empty_ink_glyph_ids.push(*glyph_id);
+                continue;
+            }
+            x_min = std::cmp::min(x_min, bounds.min.x as i32);
+            y_min = std::cmp::min(y_min, bounds.min.y as i32);
+            y_max = std::cmp::max(y_max, bounds.max.y as i32);
+            metrics.insert(
+                format!("{:04X}", glyph_id),
+                GlyphMetrics {
+                    width: (bounds.max.x - bounds.min.x) as u32,
+                    height: (bounds.max.y - bounds.min.y) as u32,
+                    adv: scaled_font.h_advance(GlyphId(*glyph_id)),
+                    bearing_x: bounds.min.x,
+                    bearing_y: -bounds.min.y,
+                },
+            );
             glyphs.push((glyph_id, glyph));
         } else {
             // For the font I tried, a common reason (apparently, from opening it in FontForge)
@@ -159,26 +365,333 @@ fn dump_glyphs(
     // We want a common height for all glyphs, because when glyph images are laid out
     // side-by-side, we want their baselines to align.
     // Adding an extra pixel at the bottom, for reasons I can't remember (perhaps not needed).
-    let height = y_max - y_min + 1;
+    // If every requested glyph turned out to have no ink, there's no real bounding box to derive
+    // a height from, so fall back to the nominal pixel size.
+    let height = if glyphs.is_empty() { size.ceil() as i32 } else { y_max - y_min + 1 };
 
     // Second pass: Generate images out of the outlined glyphs.
+    let gamma_table = gamma_lut(gamma);
     std::fs::create_dir_all(output_dir.clone())?;
+    let metrics_filename = output_dir.join("metrics.json");
+    std::fs::write(&metrics_filename, serde_json::to_vec_pretty(&metrics)?)
+        .with_context(|| format!("Failed to write to {:?}", metrics_filename))?;
+
+    // Every empty-ink glyph still gets an output entry: a fully transparent placeholder, sized
+    // from its advance width (falling back to 1px wide if the advance also rounds to 0, e.g. a
+    // truly zero-width combining mark) by the common height above.
+    for glyph_id in &empty_ink_glyph_ids {
+        let placeholder_width = (scaled_font.h_advance(GlyphId(*glyph_id)).round() as u32).max(1);
+        let image = DynamicImage::new_rgba8(placeholder_width, height as u32).to_rgba8();
+        let output_filename = output_dir.join(format!("glyph-{:04X}.png", glyph_id));
+        image
+            .save(&output_filename)
+            .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+        println!("Glyph {:04X} has no ink; wrote a transparent placeholder {:#?}.", glyph_id, output_filename);
+    }
+
+    if let Some(bpp) = bpp {
+        dump_glyphs_packed(glyphs, &scaled_font, x_min, y_min, height, bpp, output_dir)
+    } else if atlas {
+        dump_glyph_atlas(
+            glyphs,
+            output_dir,
+            atlas_width,
+            atlas_padding,
+            atlas_baseline_align,
+            x_min,
+            y_min,
+            height,
+            gamma_table,
+            preblend,
+            text_color,
+        )
+    } else {
+        for (glyph_id, glyph) in glyphs {
+            let width = glyph.px_bounds().max.x - (x_min as f32) + 1.0;
+            let mut image = DynamicImage::new_rgba8(width as u32, height as u32).to_rgba8();
+            glyph.draw(|x, y, c| {
+                // Draw pixel `(x, y)` with coverage `c` (= what fraction of the pixel the glyph covered).
+                // As mentioned in "Implementation notes" above, these `(x, y)` need to be reinterpreted.
+                let reinterpret_x = x as i32 + glyph.px_bounds().min.x as i32 - x_min;
+                let reinterpret_y = y as i32 + glyph.px_bounds().min.y as i32 - y_min;
+                // Some fonts produce outlines whose reinterpreted coordinates fall outside the
+                // image we sized from the global bounding box; skip those pixels rather than
+                // panicking inside `put_pixel`.
+                if reinterpret_x < 0
+                    || reinterpret_y < 0
+                    || reinterpret_x as u32 >= image.width()
+                    || reinterpret_y as u32 >= image.height()
+                {
+                    return;
+                }
+                let mut alpha = gamma_table[(c.clamp(0.0, 1.0) * 255.0) as usize];
+                if preblend {
+                    alpha = preblend_alpha(alpha, text_color);
+                }
+                image.put_pixel(
+                    reinterpret_x as u32,
+                    reinterpret_y as u32,
+                    // Using black (#000000) as colour, and the gamma-corrected coverage as the PNG image's "alpha" (≈ opacity) value.
+                    Rgba([0, 0, 0, alpha]),
+                )
+            });
+            let output_filename = output_dir.join(format!("glyph-{:04X}.png", glyph_id));
+            image
+                .save(&output_filename)
+                .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+            println!(
+                "For glyph {:3}, generated {:#?}.",
+                glyph_id, output_filename
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Packs `glyphs` into a single "atlas" image. By default (`baseline_align == false`) this uses
+/// shelf packing, as in GPU glyph atlases: glyphs are sorted by rasterized height descending (so
+/// a shelf's height is set by its first, tallest glyph, minimizing space wasted by short glyphs
+/// trailing a tall one), then placed left-to-right at a cursor that advances by each glyph's
+/// width plus `padding`; once the cursor would exceed `atlas_width`, it wraps to `x = 0` and the
+/// `y` cursor advances by the finished shelf's height plus `padding`. Each glyph is blitted at
+/// its packed offset using the same `draw` callback as the per-file loop in `dump_glyphs` — but
+/// since every glyph here keeps its own bounding box (rather than a box shared with the whole
+/// font), `draw`'s `(x, y)` can be used directly as an offset from the packed corner, with no
+/// `x_min`/`y_min` reinterpretation needed. This packs tightly, but glyphs laid side by side in
+/// the sheet do *not* share a baseline (a short glyph like a comma and a tall one like a
+/// full-height letter each hug their own cell's top edge).
+///
+/// If `baseline_align` is set, every glyph instead gets a cell of the shared `height` (the same
+/// common height `dump_glyphs` gives the per-file PNGs) and is blitted with the same
+/// `x_min`/`y_min` reinterpretation the per-file loop uses, so the whole sheet shares one
+/// baseline at the cost of the tighter variable-row-height packing above.
+///
+/// Writes `atlas.png` plus a sidecar `atlas.json` mapping each glyph id (as a 4-digit hex string,
+/// matching the per-glyph PNG naming) to its `(x, y, w, h)` rectangle within it.
+#[allow(clippy::too_many_arguments)]
+fn dump_glyph_atlas(
+    mut glyphs: Vec<(&u16, ab_glyph::OutlinedGlyph)>,
+    output_dir: PathBuf,
+    atlas_width: u32,
+    padding: u32,
+    baseline_align: bool,
+    x_min: i32,
+    y_min: i32,
+    height: i32,
+    gamma_table: [u8; 256],
+    preblend: bool,
+    text_color: (f64, f64, f64),
+) -> Result<()> {
+    use image::{DynamicImage, Rgba};
+
+    if !baseline_align {
+        glyphs.sort_by_key(|(_glyph_id, glyph)| {
+            let bounds = glyph.px_bounds();
+            std::cmp::Reverse((bounds.max.y - bounds.min.y) as u32)
+        });
+    }
+
+    let mut cursor_x: u32 = 0;
+    let mut cursor_y: u32 = 0;
+    let mut shelf_height: u32 = 0;
+    let mut placements: Vec<AtlasRect> = Vec::new(); // one per glyph in `glyphs`, in order.
+    for (_glyph_id, glyph) in &glyphs {
+        let bounds = glyph.px_bounds();
+        let (width, glyph_height) = if baseline_align {
+            ((bounds.max.x - (x_min as f32) + 1.0).ceil() as u32, height as u32)
+        } else {
+            ((bounds.max.x - bounds.min.x).ceil() as u32, (bounds.max.y - bounds.min.y).ceil() as u32)
+        };
+        if cursor_x > 0 && cursor_x + width > atlas_width {
+            cursor_x = 0;
+            cursor_y += shelf_height + padding;
+            shelf_height = 0;
+        }
+        placements.push(AtlasRect { x: cursor_x, y: cursor_y, w: width, h: glyph_height });
+        cursor_x += width + padding;
+        shelf_height = shelf_height.max(glyph_height);
+    }
+    let atlas_height = cursor_y + shelf_height;
+
+    let mut atlas_image = DynamicImage::new_rgba8(atlas_width, atlas_height.max(1)).to_rgba8();
+    let mut sidecar: std::collections::HashMap<String, AtlasRect> = std::collections::HashMap::new();
+    for ((glyph_id, glyph), cell) in glyphs.iter().zip(placements.iter()) {
+        glyph.draw(|x, y, c| {
+            let mut alpha = gamma_table[(c.clamp(0.0, 1.0) * 255.0) as usize];
+            if preblend {
+                alpha = preblend_alpha(alpha, text_color);
+            }
+            let (px, py) = if baseline_align {
+                (
+                    cell.x + (x as i32 + glyph.px_bounds().min.x as i32 - x_min) as u32,
+                    cell.y + (y as i32 + glyph.px_bounds().min.y as i32 - y_min) as u32,
+                )
+            } else {
+                (cell.x + x, cell.y + y)
+            };
+            if px < atlas_image.width() && py < atlas_image.height() {
+                atlas_image.put_pixel(px, py, Rgba([0, 0, 0, alpha]));
+            }
+        });
+        sidecar.insert(
+            format!("{:04X}", glyph_id),
+            AtlasRect { x: cell.x, y: cell.y, w: cell.w, h: cell.h },
+        );
+    }
+
+    let atlas_filename = output_dir.join("atlas.png");
+    atlas_image
+        .save(&atlas_filename)
+        .with_context(|| format!("Failed to write to {:?}", atlas_filename))?;
+    let sidecar_filename = output_dir.join("atlas.json");
+    std::fs::write(&sidecar_filename, serde_json::to_vec_pretty(&sidecar)?)
+        .with_context(|| format!("Failed to write to {:?}", sidecar_filename))?;
+    println!(
+        "Generated atlas {:#?} ({} glyphs, {}x{}) with sidecar {:#?}.",
+        atlas_filename,
+        glyphs.len(),
+        atlas_width,
+        atlas_height,
+        sidecar_filename
+    );
+    Ok(())
+}
+
+/// Like the per-glyph loop in `dump_glyphs`, but instead of a PNG, quantizes each glyph's
+/// coverage into `2^bpp` levels and packs the pixels MSB-first into a compact binary blob
+/// (`glyph-{:04X}.bin`): a little-endian `{width: u16, height: u16, advance: u16}` header
+/// followed by `height` rows of `ceil(width * bpp / 8)` bytes each — the layout embedded
+/// firmware expects for a ROM-able glyph table. `bpp` must be one of 1, 2, 4, or 8, each of
+/// which divides 8 evenly, so (unlike an arbitrary bit depth) no packed pixel ever straddles a
+/// byte boundary.
+fn dump_glyphs_packed<F: ab_glyph::Font, SF: ab_glyph::ScaleFont<F>>(
+    glyphs: Vec<(&u16, ab_glyph::OutlinedGlyph)>,
+    scaled_font: &SF,
+    x_min: i32,
+    y_min: i32,
+    height: i32,
+    bpp: u8,
+    output_dir: PathBuf,
+) -> Result<()> {
+    use ab_glyph::GlyphId;
+
+    let levels = (1u32 << bpp) - 1;
     for (glyph_id, glyph) in glyphs {
-        let width = glyph.px_bounds().max.x - (x_min as f32) + 1.0;
-        let mut image = DynamicImage::new_rgba8(width as u32, height as u32).to_rgba8();
+        let width = (glyph.px_bounds().max.x - x_min as f32 + 1.0) as u32;
+        let bytes_per_row = (width as usize * bpp as usize + 7) / 8;
+        let mut rows = vec![0u8; bytes_per_row * height as usize];
         glyph.draw(|x, y, c| {
-            // Draw pixel `(x, y)` with coverage `c` (= what fraction of the pixel the glyph covered).
-            // As mentioned in "Implementation notes" above, these `(x, y)` need to be reinterpreted.
             let reinterpret_x = x as i32 + glyph.px_bounds().min.x as i32 - x_min;
             let reinterpret_y = y as i32 + glyph.px_bounds().min.y as i32 - y_min;
-            image.put_pixel(
-                reinterpret_x as u32,
-                reinterpret_y as u32,
-                // Using black (#000000) as colour, and the "coverage" fraction as the PNG image's "alpha" (≈ opacity) value.
-                Rgba([0, 0, 0, (c * 255.0) as u8]),
-            )
+            // Mirror dump_glyphs's bounds guard: skip pixels that fall outside the image we
+            // sized from the global bounding box, rather than panicking on the `rows` index below.
+            if reinterpret_x < 0
+                || reinterpret_y < 0
+                || reinterpret_x as u32 >= width
+                || reinterpret_y as u32 >= height as u32
+            {
+                return;
+            }
+            let reinterpret_x = reinterpret_x as usize;
+            let reinterpret_y = reinterpret_y as usize;
+            let level = (c.clamp(0.0, 1.0) * levels as f32).round() as u8;
+            let bit_offset = reinterpret_x * bpp as usize;
+            let byte_index = reinterpret_y * bytes_per_row + bit_offset / 8;
+            let shift = 8 - (bit_offset % 8) - bpp as usize;
+            rows[byte_index] |= level << shift;
         });
-        let output_filename = output_dir.join(format!("glyph-{:04X}.png", glyph_id));
+        let advance = scaled_font.h_advance(GlyphId(*glyph_id)).round() as u16;
+        let mut blob = Vec::with_capacity(6 + rows.len());
+        blob.extend_from_slice(&(width as u16).to_le_bytes());
+        blob.extend_from_slice(&(height as u16).to_le_bytes());
+        blob.extend_from_slice(&advance.to_le_bytes());
+        blob.extend_from_slice(&rows);
+        let output_filename = output_dir.join(format!("glyph-{:04X}.bin", glyph_id));
+        std::fs::write(&output_filename, &blob)
+            .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+        println!("For glyph {:3}, generated {:#?}.", glyph_id, output_filename);
+    }
+    Ok(())
+}
+
+/// Like the per-glyph loop in `dump_glyphs`, but renders each glyph at 3x horizontal resolution
+/// and collapses the three horizontal sub-samples under each output pixel into its R, G, B
+/// channels (standard horizontal-RGB LCD subpixel layout), after running `LCD_FIR_FILTER` over
+/// the supersampled coverage row to suppress color fringing. See the `--subpixel` doc comment on
+/// `Opts` for motivation.
+fn dump_glyphs_subpixel(
+    font: &impl ab_glyph::Font,
+    glyph_ids: &[u16],
+    size: f32,
+    output_dir: &Path,
+    gamma_table: [u8; 256],
+) -> Result<()> {
+    use ab_glyph::{GlyphId, Point, PxScale};
+    use image::{DynamicImage, Rgba};
+
+    // `pad` supersampled columns of padding on each side, so the 5-tap filter can be applied
+    // uniformly (including near the glyph's left/right edges) without special-casing bounds.
+    const PAD: i32 = 2;
+    let scale = PxScale {
+        x: size * 3.0,
+        y: size,
+    };
+
+    // First pass: outline each glyph at the tripled horizontal scale, and find the global
+    // bounding box (same idea as the non-subpixel first pass in `dump_glyphs`).
+    let mut x_min = i32::MAX;
+    let mut y_min = i32::MAX;
+    let mut y_max = i32::MIN;
+    let mut glyphs = Vec::new();
+    for &glyph_id in glyph_ids {
+        let glyph = GlyphId(glyph_id).with_scale_and_position(scale, Point { x: 0.0, y: 0.0 });
+        if let Some(glyph) = font.outline_glyph(glyph) {
+            x_min = std::cmp::min(x_min, glyph.px_bounds().min.x as i32);
+            y_min = std::cmp::min(y_min, glyph.px_bounds().min.y as i32);
+            y_max = std::cmp::max(y_max, glyph.px_bounds().max.y as i32);
+            glyphs.push((glyph_id, glyph));
+        }
+    }
+    let height = (y_max - y_min + 1) as usize;
+
+    for (glyph_id, glyph) in glyphs {
+        let supersampled_width = (glyph.px_bounds().max.x - x_min as f32 + 1.0) as i32;
+        let stride = (supersampled_width + 2 * PAD) as usize;
+        let mut coverage = vec![0f32; stride * height];
+        glyph.draw(|x, y, c| {
+            let sx = x as i32 + glyph.px_bounds().min.x as i32 - x_min + PAD;
+            let sy = y as i32 + glyph.px_bounds().min.y as i32 - y_min;
+            if sx >= 0 && (sx as usize) < stride && sy >= 0 && (sy as usize) < height {
+                coverage[sy as usize * stride + sx as usize] = c;
+            }
+        });
+
+        // Collapse triples of supersampled columns into output pixels, filtering each subpixel
+        // through the 5-tap FIR filter first to spread ink across channels and avoid fringing.
+        let output_width = ((supersampled_width + 2) / 3) as u32;
+        let mut image = DynamicImage::new_rgba8(output_width, height as u32).to_rgba8();
+        for row in 0..height {
+            for out_x in 0..output_width as i32 {
+                let mut channels = [0u8; 3];
+                for (subpixel, channel) in channels.iter_mut().enumerate() {
+                    let center = out_x * 3 + subpixel as i32;
+                    let mut acc = 0f32;
+                    for (tap, &weight) in LCD_FIR_FILTER.iter().enumerate() {
+                        let pos = center + (tap as i32 - 2) + PAD;
+                        if pos >= 0 && (pos as usize) < stride {
+                            acc += coverage[row * stride + pos as usize] * weight as f32;
+                        }
+                    }
+                    let filtered_coverage = (acc / 256.0).clamp(0.0, 1.0);
+                    let alpha = gamma_table[(filtered_coverage * 255.0) as usize];
+                    // Black text on a white background: more ink means a darker (lower) channel value.
+                    *channel = 255 - alpha;
+                }
+                image.put_pixel(out_x as u32, row as u32, Rgba([channels[0], channels[1], channels[2], 255]));
+            }
+        }
+
+        let output_filename = output_dir.join(format!("glyph-{:04X}-subpixel.png", glyph_id));
         image
             .save(&output_filename)
             .with_context(|| format!("Failed to write to {:?}", output_filename))?;
@@ -188,4 +701,278 @@ fn dump_glyphs(
         );
     }
     Ok(())
+}
+
+/// A glyph's pen position within a `dump_layout` run, written alongside `layout.png` as
+/// `layout.json`, so a caller can check the advance/kerning math against the source document
+/// without having to measure pixels in the image.
+#[derive(serde_derive::Serialize)]
+struct PenPosition {
+    glyph_id: String,
+    pen_x: f32,
+}
+
+/// Renders `glyph_ids`, in order, side-by-side into one `layout.png`, advancing the pen by each
+/// glyph's real horizontal advance plus the font's pair kerning against the previous glyph
+/// (rather than the equal-width independent cells `dump_glyphs` produces), so a run of glyph ids
+/// observed in a PDF can be eyeballed as the laid-out word it actually renders as. The pen
+/// position used for each glyph is also written out to `layout.json`.
+fn dump_layout(
+    font_file_contents: &[u8],
+    glyph_ids: &[u16],
+    size: f32,
+    output_dir: &Path,
+    gamma_table: [u8; 256],
+) -> Result<()> {
+    use ab_glyph::{Font, FontRef, Glyph, GlyphId, Point, ScaleFont};
+    use image::{DynamicImage, Rgba};
+
+    let font =
+        FontRef::try_from_slice(font_file_contents).with_context(|| "Could not parse font.")?;
+    let scaled_font = font.as_scaled(size);
+
+    // First pass: position each glyph by advancing the pen (plus kerning against the previous
+    // glyph), outline it there, and find the overall bounding box.
+    let mut pen_x = 0.0f32;
+    let mut prev: Option<GlyphId> = None;
+    let mut x_min = i32::MAX;
+    let mut y_min = i32::MAX;
+    let mut x_max = i32::MIN;
+    let mut y_max = i32::MIN;
+    let mut glyphs = Vec::new();
+    let mut pen_positions = Vec::with_capacity(glyph_ids.len());
+    for &glyph_id in glyph_ids {
+        let glyph_id = GlyphId(glyph_id);
+        if let Some(prev) = prev {
+            pen_x += scaled_font.kern(prev, glyph_id);
+        }
+        pen_positions.push(PenPosition {
+            glyph_id: format!("{:04X}", glyph_id.0),
+            pen_x,
+        });
+        let positioned: Glyph = glyph_id.with_scale_and_position(size, Point { x: pen_x, y: 0.0 });
+        if let Some(outlined) = font.outline_glyph(positioned) {
+            x_min = std::cmp::min(x_min, outlined.px_bounds().min.x as i32);
+            y_min = std::cmp::min(y_min, outlined.px_bounds().min.y as i32);
+            x_max = std::cmp::max(x_max, outlined.px_bounds().max.x as i32);
+            y_max = std::cmp::max(y_max, outlined.px_bounds().max.y as i32);
+            glyphs.push(outlined);
+        }
+        pen_x += scaled_font.h_advance(glyph_id);
+        prev = Some(glyph_id);
+    }
+
+    let width = (x_max - x_min + 1) as u32;
+    let height = (y_max - y_min + 1) as u32;
+    let mut image = DynamicImage::new_rgba8(width, height).to_rgba8();
+    for glyph in &glyphs {
+        glyph.draw(|x, y, c| {
+            let reinterpret_x = x as i32 + glyph.px_bounds().min.x as i32 - x_min;
+            let reinterpret_y = y as i32 + glyph.px_bounds().min.y as i32 - y_min;
+            let alpha = gamma_table[(c.clamp(0.0, 1.0) * 255.0) as usize];
+            image.put_pixel(reinterpret_x as u32, reinterpret_y as u32, Rgba([0, 0, 0, alpha]));
+        });
+    }
+
+    let output_filename = output_dir.join("layout.png");
+    image
+        .save(&output_filename)
+        .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+
+    let positions_filename = output_dir.join("layout.json");
+    std::fs::write(&positions_filename, serde_json::to_vec_pretty(&pen_positions)?)
+        .with_context(|| format!("Failed to write to {:?}", positions_filename))?;
+
+    println!("Generated layout {:#?} for glyphs {:?}.", output_filename, glyph_ids);
+    Ok(())
+}
+
+/// Exports each glyph as a vector `.svg` `<path>`, walking its outline's line/quadratic/cubic
+/// segments into SVG path commands (`M`/`L`/`Q`/`C`) and flipping the y-axis (font outlines are
+/// y-up from the baseline; SVG is y-down), so the baseline maps correctly. All glyphs share one
+/// viewBox derived from the global bounding box, for the same reason the bitmap modes use a
+/// common height: so glyphs stay comparably sized and aligned when inspected side by side.
+fn dump_glyphs_svg(
+    font_file_contents: &[u8],
+    glyph_ids: Option<Vec<u16>>,
+    size: f32,
+    output_dir: &Path,
+) -> Result<()> {
+    use ab_glyph::{Font, FontRef, GlyphId, Outline, OutlineCurve, Point};
+
+    let font =
+        FontRef::try_from_slice(font_file_contents).with_context(|| "Could not parse font.")?;
+    let glyph_ids = glyph_ids.unwrap_or((0..(font.glyph_count() as u16)).collect());
+    let scale = size / font.units_per_em().unwrap_or(1000.0);
+    let to_px = |p: Point| (p.x * scale, p.y * scale);
+
+    // First pass: collect each glyph's outline, and find the global bounding box (in pixels).
+    let mut x_min = f32::MAX;
+    let mut y_min = f32::MAX;
+    let mut x_max = f32::MIN;
+    let mut y_max = f32::MIN;
+    let mut outlines: Vec<(u16, Outline)> = Vec::new();
+    for glyph_id in glyph_ids {
+        if let Some(outline) = font.outline(GlyphId(glyph_id)) {
+            let (min_x, min_y) = to_px(outline.bounds.min);
+            let (max_x, max_y) = to_px(outline.bounds.max);
+            x_min = x_min.min(min_x);
+            y_min = y_min.min(min_y);
+            x_max = x_max.max(max_x);
+            y_max = y_max.max(max_y);
+            outlines.push((glyph_id, outline));
+        }
+    }
+    // Flipping y -> -y means the topmost (highest-y) point becomes the smallest svg-y, i.e. the
+    // viewBox's min-y is `-y_max`.
+    let view_box = format!("{} {} {} {}", x_min, -y_max, x_max - x_min, y_max - y_min);
+
+    for (glyph_id, outline) in &outlines {
+        let mut d = String::new();
+        let mut pen: Option<(f32, f32)> = None;
+        for curve in &outline.curves {
+            let (from, to) = match curve {
+                OutlineCurve::Line(from, to) => (*from, *to),
+                OutlineCurve::Quad(from, _, to) => (*from, *to),
+                OutlineCurve::Cubic(from, _, _, to) => (*from, *to),
+            };
+            let (from_x, from_y) = to_px(from);
+            let from_svg = (from_x, -from_y);
+            if pen != Some(from_svg) {
+                d.push_str(&format!("M {:.2},{:.2} ", from_svg.0, from_svg.1));
+            }
+            let (to_x, to_y) = to_px(to);
+            let to_svg = (to_x, -to_y);
+            match curve {
+                OutlineCurve::Line(..) => {
+                    d.push_str(&format!("L {:.2},{:.2} ", to_svg.0, to_svg.1));
+                }
+                OutlineCurve::Quad(_, control, _) => {
+                    let (cx, cy) = to_px(*control);
+                    d.push_str(&format!("Q {:.2},{:.2} {:.2},{:.2} ", cx, -cy, to_svg.0, to_svg.1));
+                }
+                OutlineCurve::Cubic(_, c1, c2, _) => {
+                    let (c1x, c1y) = to_px(*c1);
+                    let (c2x, c2y) = to_px(*c2);
+                    d.push_str(&format!(
+                        "C {:.2},{:.2} {:.2},{:.2} {:.2},{:.2} ",
+                        c1x, -c1y, c2x, -c2y, to_svg.0, to_svg.1
+                    ));
+                }
+            }
+            pen = Some(to_svg);
+        }
+
+        let svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{}\"><path d=\"{}\" /></svg>\n",
+            view_box,
+            d.trim_end()
+        );
+        let output_filename = output_dir.join(format!("glyph-{:04X}.svg", glyph_id));
+        std::fs::write(&output_filename, svg)
+            .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+        println!(
+            "For glyph {:3}, generated {:#?}.",
+            glyph_id, output_filename
+        );
+    }
+    Ok(())
+}
+
+/// A decoded PC Screen Font (PSF1 or PSF2), a fixed-cell bitmap format common for Linux console
+/// and embedded fonts. Glyphs are stored one bit per pixel, MSB-first within each row, each row
+/// padded out to a whole number of bytes. See
+/// https://www.win.tue.nl/~aeb/linux/kbd/font-formats-1.html for the on-disk layout this parses.
+struct PsfFont {
+    width: u32,
+    height: u32,
+    bytes_per_row: usize,
+    /// One entry per glyph, each `height * bytes_per_row` bytes, in on-disk (packed-bit) form.
+    glyph_bitmaps: Vec<Vec<u8>>,
+}
+
+impl PsfFont {
+    /// Detect PSF1 (magic `36 04`) vs PSF2 (magic `72 B5 4A 86`) and parse accordingly.
+    fn parse(bytes: &[u8]) -> Result<PsfFont> {
+        if let Some(data) = bytes.strip_prefix(&[0x36, 0x04]) {
+            anyhow::ensure!(data.len() >= 2, "PSF1 file too short: missing mode/charsize bytes");
+            let mode = data[0];
+            let charsize = data[1] as usize;
+            let glyph_count = if mode & 0x01 != 0 { 512 } else { 256 };
+            let data = &data[2..];
+            anyhow::ensure!(
+                data.len() >= glyph_count * charsize,
+                "PSF1 file too short for {} glyphs of {} bytes each",
+                glyph_count,
+                charsize
+            );
+            let glyph_bitmaps = data
+                .chunks_exact(charsize)
+                .take(glyph_count)
+                .map(<[u8]>::to_vec)
+                .collect();
+            Ok(PsfFont { width: 8, height: charsize as u32, bytes_per_row: 1, glyph_bitmaps })
+        } else if let Some(data) = bytes.strip_prefix(&[0x72, 0xB5, 0x4A, 0x86]) {
+            anyhow::ensure!(data.len() >= 28, "PSF2 file too short for its header");
+            let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            let _version = read_u32(0);
+            let headersize = read_u32(4) as usize;
+            let _flags = read_u32(8);
+            let length = read_u32(12) as usize;
+            let charsize = read_u32(16) as usize;
+            let height = read_u32(20);
+            let width = read_u32(24);
+            let bytes_per_row = ((width + 7) / 8) as usize;
+            // `headersize` is measured from the start of the file (magic bytes included).
+            anyhow::ensure!(headersize >= 4 + 28, "PSF2 header size {} is too small", headersize);
+            let data = &bytes[headersize..];
+            anyhow::ensure!(
+                data.len() >= length * charsize,
+                "PSF2 file too short for {} glyphs of {} bytes each",
+                length,
+                charsize
+            );
+            let glyph_bitmaps = data.chunks_exact(charsize).take(length).map(<[u8]>::to_vec).collect();
+            Ok(PsfFont { width, height, bytes_per_row, glyph_bitmaps })
+        } else {
+            anyhow::bail!("Not a recognized PSF1/PSF2 font file (unexpected magic bytes)")
+        }
+    }
+}
+
+/// Rasterize every glyph of a PSF bitmap font into its own PNG (set bits become opaque black
+/// pixels), using the same `glyph-{:04X}.png` naming as the outline-font path, so downstream
+/// tooling doesn't need to care whether a font was an outline font or a bitmap font.
+fn dump_psf_glyphs(font_file_contents: &[u8], output_dir: &Path) -> Result<()> {
+    use image::{DynamicImage, Rgba};
+
+    let font = PsfFont::parse(font_file_contents)?;
+    println!(
+        "This PSF font has {} glyphs, each {}x{}.",
+        font.glyph_bitmaps.len(),
+        font.width,
+        font.height
+    );
+    for (glyph_id, bitmap) in font.glyph_bitmaps.iter().enumerate() {
+        let mut image = DynamicImage::new_rgba8(font.width, font.height).to_rgba8();
+        for row in 0..font.height as usize {
+            for col in 0..font.width as usize {
+                let byte = bitmap[row * font.bytes_per_row + col / 8];
+                let bit_is_set = byte & (0x80 >> (col % 8)) != 0;
+                if bit_is_set {
+                    image.put_pixel(col as u32, row as u32, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        let output_filename = output_dir.join(format!("glyph-{:04X}.png", glyph_id));
+        image
+            .save(&output_filename)
+            .with_context(|| format!("Failed to write to {:?}", output_filename))?;
+    }
+    println!(
+        "Generated {} PSF glyph images in {:?}.",
+        font.glyph_bitmaps.len(),
+        output_dir
+    );
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+//! Serializes a glyph-code→Unicode mapping (the same data Phase 2 collects in
+//! `font_glyph_mappings`) into a `/ToUnicode` CMap stream, so a fixed PDF is searchable/copyable
+//! without needing any `/Span /ActualText` marked-content wrapping at all.
+//!
+//! See "5.9.2 CMap Mapping" and "5.9.1 Overview" (for the standard PostScript skeleton) in
+//! PDF32000_2008.pdf. This mirrors what `tounicode`/`tounicode16` produce in the ConTeXt font
+//! loader, but generated from the mappings this tool already harvests in Phase 1/2.
+
+use std::collections::BTreeMap;
+
+const MAX_ENTRIES_PER_BLOCK: usize = 100;
+
+fn hex_code(code: u16) -> String {
+    format!("<{:04X}>", code)
+}
+
+/// Encode `text` as the UTF-16BE hex string (without the `<>` delimiters) used inside a CMap,
+/// e.g. for a `beginbfchar`/`beginbfrange` destination. Unlike `/ActualText` strings (see
+/// `pdf_encode_unicode_text_string`), CMap destinations do not carry a leading U+FEFF BOM.
+fn utf16be_hex(text: &str) -> String {
+    let mut hex = String::new();
+    for usv in text.encode_utf16() {
+        hex.push_str(&format!("{:04X}", usv));
+    }
+    hex
+}
+
+/// Build the bytes of a standard `/ToUnicode` CMap stream (see 9.10.3 in PDF32000_2008.pdf)
+/// from a glyph-code→Unicode-string mapping. Consecutive source codes whose destinations are
+/// also consecutive single-codepoint strings are collapsed into a single `bfrange` entry.
+pub fn build_tounicode_cmap(mapped: &std::collections::HashMap<u16, String>) -> Vec<u8> {
+    // A BTreeMap so that codes are emitted in ascending order, which is what makes run-collapsing
+    // into bfrange meaningful (and matches how real CMaps are laid out).
+    let mapped: BTreeMap<u16, &String> = mapped.iter().map(|(&code, text)| (code, text)).collect();
+
+    let mut out = String::new();
+    out.push_str("/CIDInit /ProcSet findresource begin\n");
+    out.push_str("12 dict begin\n");
+    out.push_str("begincmap\n");
+    out.push_str("/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n");
+    out.push_str("/CMapName /Adobe-Identity-UCS def\n");
+    out.push_str("/CMapType 2 def\n");
+    out.push_str("1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n");
+
+    // Collapse consecutive runs of (code, single-codepoint text) into bfrange entries; anything
+    // that can't be collapsed (a gap in the codes, or a multi-codepoint destination) is emitted
+    // via bfchar instead.
+    #[derive(Clone)]
+    enum Entry {
+        Range { lo: u16, hi: u16, dst_lo: u32 },
+        Char { code: u16, text: String },
+    }
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut iter = mapped.into_iter().peekable();
+    while let Some((code, text)) = iter.next() {
+        let single_scalar = single_scalar_value(text);
+        if let Some(dst_lo) = single_scalar {
+            let mut hi = code;
+            let mut next_dst = dst_lo;
+            while let Some(&(next_code, next_text)) = iter.peek() {
+                if next_code != hi.wrapping_add(1) {
+                    break;
+                }
+                match single_scalar_value(next_text) {
+                    Some(v) if v == next_dst + 1 => {
+                        next_dst = v;
+                        hi = next_code;
+                        iter.next();
+                    }
+                    _ => break,
+                }
+            }
+            if hi == code {
+                // A lone code with no consecutive neighbour: nothing to collapse.
+                entries.push(Entry::Char {
+                    code,
+                    text: text.clone(),
+                });
+            } else {
+                entries.push(Entry::Range {
+                    lo: code,
+                    hi,
+                    dst_lo,
+                });
+            }
+        } else {
+            entries.push(Entry::Char {
+                code,
+                text: text.clone(),
+            });
+        }
+    }
+
+    let ranges: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| matches!(e, Entry::Range { .. }))
+        .collect();
+    let chars: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| matches!(e, Entry::Char { .. }))
+        .collect();
+
+    for block in chars.chunks(MAX_ENTRIES_PER_BLOCK) {
+        out.push_str(&format!("{} beginbfchar\n", block.len()));
+        for entry in block {
+            if let Entry::Char { code, text } = entry {
+                out.push_str(&format!(
+                    "{} <{}>\n",
+                    hex_code(*code),
+                    utf16be_hex(text)
+                ));
+            }
+        }
+        out.push_str("endbfchar\n");
+    }
+    for block in ranges.chunks(MAX_ENTRIES_PER_BLOCK) {
+        out.push_str(&format!("{} beginbfrange\n", block.len()));
+        for entry in block {
+            if let Entry::Range { lo, hi, dst_lo } = entry {
+                // `dst_lo` is a scalar value, not already UTF-16 code units — for anything above
+                // the BMP (U+10000 and up) that's a surrogate pair, so this must go through the
+                // same `utf16be_hex` encoding `bfchar` uses above, not be formatted as a raw
+                // 4-digit hex value (which silently truncated/mis-encoded astral destinations).
+                let dst_text = char::from_u32(*dst_lo).expect("validated by single_scalar_value");
+                out.push_str(&format!(
+                    "{} {} <{}>\n",
+                    hex_code(*lo),
+                    hex_code(*hi),
+                    utf16be_hex(&dst_text.to_string())
+                ));
+            }
+        }
+        out.push_str("endbfrange\n");
+    }
+
+    out.push_str("endcmap\n");
+    out.push_str("CMapName currentdict /CMap defineresource pop\n");
+    out.push_str("end\n");
+    out.push_str("end\n");
+    out.into_bytes()
+}
+
+/// If `text` is exactly one Unicode scalar value, return it (as a plain `u32` codepoint) so that
+/// adjacent single-codepoint mappings can be collapsed into a `bfrange`. Multi-codepoint strings
+/// (ligatures, combining sequences) can only ever be represented via `bfchar`.
+fn single_scalar_value(text: &str) -> Option<u32> {
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(first as u32)
+}
+
+/// Create a new stream object for `cmap_bytes` in `document`, and set it as the `/ToUnicode`
+/// entry of the font dictionary at `font_id`.
+pub fn attach_tounicode_cmap(
+    document: &mut lopdf::Document,
+    font_id: lopdf::ObjectId,
+    cmap_bytes: Vec<u8>,
+) -> anyhow::Result<()> {
+    let stream = lopdf::Stream::new(lopdf::dictionary! {}, cmap_bytes);
+    let stream_id = document.add_object(lopdf::Object::Stream(stream));
+    let font_dict = document.get_object_mut(font_id)?.as_dict_mut()?;
+    font_dict.set("ToUnicode", lopdf::Object::Reference(stream_id));
+    Ok(())
+}
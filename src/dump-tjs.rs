@@ -10,6 +10,9 @@ use log::LevelFilter;
 use std::collections::HashMap;
 use std::io::Write;
 
+mod cmap_writer;
+mod font_mapping;
+mod font_program;
 mod pdf_visit;
 mod text_state;
 
@@ -29,6 +32,62 @@ impl std::str::FromStr for Phase {
     }
 }
 
+/// Phase 2 can produce one of three kinds of output:
+#[derive(Clone)]
+pub enum FixMode {
+    /// Wrap each text-showing operator in `/Span << /ActualText >> BDC ... EMC` (the default).
+    ActualText,
+    /// Don't touch content streams at all; instead build a `/ToUnicode` CMap per font from the
+    /// same glyph mappings, so the PDF is searchable/copyable without marked content.
+    ToUnicode,
+    /// Diagnostic mode: don't fix anything, just recolor text runs that contain glyphs with no
+    /// known mapping, so coverage gaps are visible at a glance on the rendered page.
+    Highlight,
+}
+impl std::str::FromStr for FixMode {
+    type Err = std::num::ParseIntError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "tounicode" {
+            Ok(FixMode::ToUnicode)
+        } else if s == "highlight" {
+            Ok(FixMode::Highlight)
+        } else {
+            Ok(FixMode::ActualText)
+        }
+    }
+}
+
+/// Parse a `--pages` argument (see its doc comment on `Opts` for the accepted syntax) into the
+/// sorted, deduped list of (1-indexed) page numbers it selects, validated against `page_count`.
+fn parse_page_selection(spec: &str, page_count: u32) -> Result<Vec<u32>> {
+    let mut pages = std::collections::BTreeSet::new();
+    for part in spec.split(',') {
+        let part = part.trim();
+        anyhow::ensure!(!part.is_empty(), "empty page-range entry in {:?}", spec);
+        let (lo, hi) = match part.split_once('-') {
+            None => {
+                let n: u32 = part.parse()?;
+                (n, n)
+            }
+            Some((lo, hi)) => {
+                let lo: u32 = if lo.is_empty() { 1 } else { lo.parse()? };
+                let hi: u32 = if hi.is_empty() { page_count } else { hi.parse()? };
+                (lo, hi)
+            }
+        };
+        anyhow::ensure!(
+            lo >= 1 && hi <= page_count && lo <= hi,
+            "page range {:?} (parsed as {}-{}) is out of bounds for a {}-page document",
+            part,
+            lo,
+            hi,
+            page_count
+        );
+        pages.extend(lo..=hi);
+    }
+    Ok(pages.into_iter().collect())
+}
+
 /// Parse a PDF file either to dump text operations (Tj etc) in it,
 /// or to "fix" all text by surrounding them with /ActualText.
 fn main() -> Result<()> {
@@ -71,9 +130,35 @@ fn main() -> Result<()> {
         /// Whether to dump (phase 1) or fix (phase 2).
         #[clap(long)]
         phase: Phase,
-        /// Operate on just a single page (should this take ranges?)
+        /// Which pages to operate on: a page number ("3"), an inclusive range ("3-10", "3-" for
+        /// "to the end", "-10" for "from the start"), or a comma-separated list of either
+        /// ("1,4,7-9"). Defaults to every page.
         #[clap(long)]
-        page: Option<u32>,
+        pages: Option<String>,
+        /// Phase 2 only: "actual-text" (default) to wrap text in /Span /ActualText, "tounicode"
+        /// to instead build a /ToUnicode CMap per font, or "highlight" to recolor runs with
+        /// unmapped glyphs (see --highlight-threshold, --highlight-color) for a quick QA pass.
+        #[clap(long, default_value = "actual-text")]
+        fix_mode: FixMode,
+        /// highlight mode only: recolor a run if the fraction of its glyphs with a known mapping
+        /// is strictly below this threshold (1.0 recolors any run with even one unmapped glyph).
+        #[clap(long, default_value = "1.0")]
+        highlight_threshold: f64,
+        /// highlight mode only: "r,g,b" nonstroking color (each in 0.0..=1.0) for flagged runs.
+        #[clap(long, default_value = "1.0,0.0,0.0")]
+        highlight_color: String,
+        /// Phase 2 only: don't seed glyph mappings from each font's embedded /ToUnicode CMap
+        /// (analogous to Ghostscript's -dIgnoreToUnicode), for PDFs whose ToUnicode is
+        /// syntactically valid but semantically wrong.
+        #[clap(long)]
+        ignore_tounicode: bool,
+        /// Phase 2 only: reconstruct word breaks a `TJ` operator's positioning numbers imply. A
+        /// run of glyphs gets a space inferred before it when the accumulated adjustment since the
+        /// previous run is a gap at least this many times the font's average glyph width (both in
+        /// the same /1000 glyph-space units, so font size doesn't matter). Set higher to require a
+        /// bigger gap, or to effectively disable this.
+        #[clap(long, default_value = "0.5")]
+        space_threshold: f64,
         /// verbose output
         #[clap(long)]
         debug: bool,
@@ -95,43 +180,94 @@ fn main() -> Result<()> {
     println!("Loaded {:?} in {:?}", &filename, end.duration_since(start));
 
     if let Phase::Phase1Dump = opts.phase {
-        text_state::dump_unicode_mappings(&document, opts.maps_dir.clone()).unwrap_or(());
+        text_state::dump_unicode_mappings(&mut document, opts.maps_dir.clone()).unwrap_or(());
     }
 
+    let page_count = document.get_pages().len() as u32;
+    let pages: Vec<u32> = match &opts.pages {
+        Some(spec) => parse_page_selection(spec, page_count)?,
+        None => (1..=page_count).collect(),
+    };
+
     let guard = match opts.profile {
         true => pprof::ProfilerGuard::new(100).ok(),
         false => None,
     };
 
-    {
-        let mut visitor = text_state::MyOpVisitor {
+    let highlight_color = {
+        let parts: Vec<f64> = opts
+            .highlight_color
+            .split(',')
+            .map(|s| s.trim().parse().expect("--highlight-color must be \"r,g,b\""))
+            .collect();
+        assert_eq!(parts.len(), 3, "--highlight-color must be \"r,g,b\"");
+        (parts[0], parts[1], parts[2])
+    };
+
+    let make_visitor = {
+        let maps_dir = opts.maps_dir.clone();
+        let phase = opts.phase.clone();
+        let fix_mode = opts.fix_mode.clone();
+        let highlight_threshold = opts.highlight_threshold;
+        let ignore_tounicode = opts.ignore_tounicode;
+        let space_threshold = opts.space_threshold;
+        move || text_state::MyOpVisitor {
             text_state: text_state::TextState {
                 current_font: pdf_visit::Font {
+                    font_id: None,
                     font_descriptor_id: None,
                     base_font_name: None,
                     encoding: None,
+                    code_width: 1,
                     subtype: None,
                     to_unicode: None,
+                    simple_font_encoding_table: None,
+                    type0_encoding: None,
+                    cid_to_gid_map: None,
+                    font_program: None,
+                    average_glyph_width: None,
                     font_descriptor: None,
                 },
                 current_tm_c: 0.0,
+                ignore_tounicode,
+                space_threshold,
             },
-            maps_dir: opts.maps_dir,
-            files: text_state::TjFiles {
-                file: HashMap::new(),
-            },
+            maps_dir: maps_dir.clone(),
+            files: text_state::TjFiles::default(),
             font_glyph_mappings: HashMap::new(),
-            phase: opts.phase.clone(),
-        };
-        pdf_visit::visit_page_content_stream_ops(
-            &mut document,
-            &mut visitor,
-            opts.page,
-            opts.debug,
-        )
-        .unwrap();
-        if let Phase::Phase2Fix = opts.phase {
+            phase: phase.clone(),
+            fix_mode: fix_mode.clone(),
+            font_ids: HashMap::new(),
+            highlight_threshold,
+            highlight_color,
+        }
+    };
+
+    match opts.phase {
+        // Phase 1 never edits content streams, so pages can be visited concurrently; each page
+        // gets its own visitor, and their `TjFiles` accumulators are merged before being written.
+        Phase::Phase1Dump => {
+            let visitors =
+                pdf_visit::visit_pages_parallel(&document, &pages, opts.debug, make_visitor)?;
+            let mut merged_files = text_state::TjFiles::default();
+            for visitor in visitors {
+                merged_files.merge(visitor.files);
+            }
+            merged_files.flush(&opts.maps_dir)?;
+        }
+        Phase::Phase2Fix => {
+            let mut visitor = make_visitor();
+            pdf_visit::visit_page_content_stream_ops(
+                &mut document,
+                &mut visitor,
+                Some(&pages),
+                opts.debug,
+            )
+            .unwrap();
             visitor.dump_font_glyph_mappings();
+            if let FixMode::ToUnicode = opts.fix_mode {
+                ok!(visitor.attach_tounicode_cmaps(&mut document));
+            }
             if let Some(output_pdf_filename) = opts.output_pdf_file {
                 println!("Saving result to PDF file: {:?}", output_pdf_filename);
                 ok!(document.save(output_pdf_filename));
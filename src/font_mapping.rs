@@ -0,0 +1,151 @@
+//! A strongly-typed load/save subsystem for the hand-edited TOML glyph-mapping files (which
+//! Phase 2 loads in `text_state::TextState::load_font_map`). Each file maps a
+//! 4-digit uppercase hex glyph id (e.g. `"00D7"`) to a [`Replacement`] — the Unicode text that
+//! glyph should be treated as meaning, plus the scalar codes and names that justify it.
+
+use anyhow::{ensure, Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A single glyph's replacement: the text it stands for, the Unicode scalar values that make up
+/// that text (so a reviewer can see the codepoints without having to paste the string into a
+/// lookup tool), and a human-readable name for each.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Replacement {
+    pub replacement_text: String,
+    pub replacement_codes: Vec<u32>,
+    pub replacement_desc: Vec<String>,
+}
+
+impl Replacement {
+    /// Check that every code in `replacement_codes` is a valid Unicode scalar value, and that
+    /// concatenating them spells out exactly `replacement_text`.
+    fn validate(&self, glyph_id: u16) -> Result<()> {
+        let mut rebuilt = String::new();
+        for &code in &self.replacement_codes {
+            let c = char::from_u32(code)
+                .with_context(|| format!("glyph {:04X}: {:#06X} is not a valid Unicode scalar value", glyph_id, code))?;
+            rebuilt.push(c);
+        }
+        ensure!(
+            rebuilt == self.replacement_text,
+            "glyph {:04X}: replacement_codes spell {:?}, but replacement_text is {:?}",
+            glyph_id,
+            rebuilt,
+            self.replacement_text
+        );
+        Ok(())
+    }
+}
+
+/// A loaded (and validated) glyph-mapping TOML file for one font, keyed by glyph id.
+#[derive(Debug, Clone, Default)]
+pub struct FontMapping {
+    replacements: HashMap<u16, Replacement>,
+}
+
+impl FontMapping {
+    /// The replacement for `glyph_id`, if this mapping has one.
+    pub fn lookup(&self, glyph_id: u16) -> Option<&Replacement> {
+        self.replacements.get(&glyph_id)
+    }
+
+    /// Every glyph id this mapping has a replacement for, for callers that seed a mapping table
+    /// up front rather than looking up one glyph id at a time (see `text_state::load_font_map`).
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Replacement)> {
+        self.replacements.iter().map(|(&glyph_id, replacement)| (glyph_id, replacement))
+    }
+
+    /// Load and validate a glyph-mapping TOML file from `path`.
+    pub fn load_from_path(path: &Path) -> Result<FontMapping> {
+        let contents = std::fs::read(path).with_context(|| format!("reading {:?}", path))?;
+        let raw: HashMap<String, Replacement> =
+            toml::from_slice(&contents).with_context(|| format!("parsing {:?} as TOML", path))?;
+        let mut replacements = HashMap::with_capacity(raw.len());
+        for (glyph_id_str, replacement) in raw {
+            let glyph_id = u16::from_str_radix(&glyph_id_str, 16)
+                .with_context(|| format!("{:?}: {:?} is not a 4-digit hex glyph id", path, glyph_id_str))?;
+            replacement.validate(glyph_id)?;
+            replacements.insert(glyph_id, replacement);
+        }
+        Ok(FontMapping { replacements })
+    }
+
+    /// Serialize this mapping back out to `path`, keyed by 4-digit uppercase hex glyph id.
+    pub fn save_to_path(&self, path: &Path) -> Result<()> {
+        let raw: HashMap<String, &Replacement> = self
+            .replacements
+            .iter()
+            .map(|(&glyph_id, replacement)| (format!("{:04X}", glyph_id), replacement))
+            .collect();
+        let toml_string = toml::to_string(&raw).with_context(|| format!("serializing {:?}", path))?;
+        std::fs::write(path, toml_string).with_context(|| format!("writing {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(text: &str, codes: Vec<u32>, desc: Vec<&str>) -> Replacement {
+        Replacement {
+            replacement_text: text.to_string(),
+            replacement_codes: codes,
+            replacement_desc: desc.into_iter().map(String::from).collect(),
+        }
+    }
+
+    #[test]
+    fn validate_accepts_codes_that_spell_out_the_text() {
+        let r = replacement("a", vec![97], vec!["0061 LATIN SMALL LETTER A"]);
+        assert!(r.validate(0x0044).is_ok());
+
+        // Multi-codepoint text (a conjunct: स्, SA + VIRAMA).
+        let r = replacement(
+            "स्",
+            vec![2360, 2381],
+            vec!["0938 DEVANAGARI LETTER SA", "094D DEVANAGARI SIGN VIRAMA"],
+        );
+        assert!(r.validate(0x00D7).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_codes_that_spell_out_different_text() {
+        let r = replacement("b", vec![97], vec!["0061 LATIN SMALL LETTER A"]);
+        assert!(r.validate(0x0045).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_an_invalid_scalar_value() {
+        // 0xD800 is a surrogate half, not a valid Unicode scalar value on its own.
+        let r = replacement("?", vec![0xD800], vec!["surrogate half"]);
+        assert!(r.validate(0x0046).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let mut replacements = HashMap::new();
+        replacements.insert(0x0044, replacement("a", vec![97], vec!["0061 LATIN SMALL LETTER A"]));
+        replacements.insert(
+            0x00D7,
+            replacement(
+                "स्",
+                vec![2360, 2381],
+                vec!["0938 DEVANAGARI LETTER SA", "094D DEVANAGARI SIGN VIRAMA"],
+            ),
+        );
+        let mapping = FontMapping { replacements };
+
+        let dir = std::env::temp_dir().join(format!("font_mapping_test_{:?}", std::thread::current().id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.toml");
+        mapping.save_to_path(&path).unwrap();
+        let loaded = FontMapping::load_from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.lookup(0x0044).unwrap().replacement_text, "a");
+        assert_eq!(loaded.lookup(0x00D7).unwrap().replacement_text, "स्");
+        assert!(loaded.lookup(0x0046).is_none());
+    }
+}
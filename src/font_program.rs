@@ -0,0 +1,100 @@
+//! Lowest-priority fallback mapping source (see `text_state::MappingSource::FontProgram`):
+//! recover a glyph's Unicode meaning straight from the embedded font program, for when neither
+//! `/ToUnicode` nor the `/Encoding`-derived sources in `pdf_visit` cover it (composite fonts with
+//! an opaque `/CIDToGIDMap`, or simple fonts with a symbolic/custom encoding). Built in
+//! `pdf_visit::parse_font` every time it parses a font dictionary (mirroring how `to_unicode` and
+//! `simple_font_encoding_table` are already parsed eagerly there); there is no cache keyed by
+//! font descriptor, so font dictionaries that share a descriptor each parse it again. Built from
+//! whichever of `/FontFile2` (TrueType) or `/FontFile3` (CFF/OpenType-CFF) is present: by
+//! inverting the font's own cmap (Unicode -> glyph id) and falling back to its glyph-name table
+//! (TrueType `post`, or a CFF charset) run through the same Adobe Glyph List logic
+//! `pdf_visit::glyph_name_to_unicode` uses for `/Differences`. A `/FontFile` (Type1) program
+//! yields no mapping (see `parse_type1`).
+//!
+//! Note: this is keyed the same way every other mapping source in this codebase is — by the
+//! `glyph_id`s `text_state::TextState::glyph_ids` produces, which for composite fonts is a true
+//! glyph id (`pdf_visit::glyph_ids_for_composite_font` already resolved it through
+//! `/CIDToGIDMap`), but for simple fonts is just the raw character code. That only lines up with
+//! the font program's own glyph ids for simple fonts whose embedded subset happens to use code ==
+//! glyph id (common for symbolic TrueType/Type1 subsets) — but those are exactly the fonts most
+//! likely to need this fallback in the first place, since they're also the ones with no useful
+//! standard `/Encoding`.
+
+use lopdf::{Dictionary, Document};
+use std::collections::HashMap;
+
+use crate::pdf_visit::glyph_name_to_unicode;
+
+/// Glyph id -> Unicode text, recovered from an embedded font program.
+pub type FontProgram = HashMap<u16, String>;
+
+/// Parse whichever embedded font program `font_descriptor` references, and return a glyph id ->
+/// Unicode text map built from its cmap/post (TrueType) or cmap/charset (CFF) tables. `None` if
+/// the descriptor has none of `/FontFile`, `/FontFile2`, `/FontFile3`, or parsing fails.
+pub fn parse_font_program(font_descriptor: &Dictionary, document: &Document) -> Option<FontProgram> {
+    if let Some(data) = stream_data(font_descriptor, document, b"FontFile2") {
+        return parse_truetype_or_opentype(&data);
+    }
+    if let Some(data) = stream_data(font_descriptor, document, b"FontFile3") {
+        return parse_truetype_or_opentype(&data);
+    }
+    if let Some(data) = stream_data(font_descriptor, document, b"FontFile") {
+        return parse_type1(&data);
+    }
+    None
+}
+
+fn stream_data(font_descriptor: &Dictionary, document: &Document, key: &[u8]) -> Option<Vec<u8>> {
+    let stream = font_descriptor
+        .get_deref(key, document)
+        .ok()?
+        .as_stream()
+        .ok()?;
+    Some(match stream.decompressed_content() {
+        Ok(data) => data,
+        Err(_) => stream.content.clone(),
+    })
+}
+
+/// Handles both `/FontFile2` (bare TrueType/OpenType-TrueType) and `/FontFile3` (bare CFF, or
+/// OpenType-CFF) font programs: `ttf_parser::Face` parses either.
+fn parse_truetype_or_opentype(data: &[u8]) -> Option<FontProgram> {
+    let face = ttf_parser::Face::parse(data, 0).ok()?;
+    let mut mapping = FontProgram::new();
+    // Invert the cmap: a show-text operand already gives us the glyph id, and we want the
+    // Unicode text it means, the opposite direction of the cmap's usual Unicode -> glyph id use.
+    if let Some(cmap) = face.tables().cmap {
+        for subtable in cmap.subtables {
+            subtable.codepoints(|codepoint| {
+                let c = match char::from_u32(codepoint) {
+                    Some(c) => c,
+                    None => return,
+                };
+                if let Some(gid) = subtable.glyph_index(codepoint) {
+                    mapping.entry(gid.0).or_insert_with(|| c.to_string());
+                }
+            });
+        }
+    }
+    // Fall back to the glyph-name table (TrueType `post`, or the CFF charset) for glyphs the
+    // cmap didn't cover (e.g. ligatures, or PUA glyphs with no standard codepoint of their own).
+    for gid in 0..face.number_of_glyphs() {
+        if mapping.contains_key(&gid) {
+            continue;
+        }
+        if let Some(name) = face.glyph_name(ttf_parser::GlyphId(gid)) {
+            if let Some(text) = glyph_name_to_unicode(name) {
+                mapping.insert(gid, text);
+            }
+        }
+    }
+    Some(mapping)
+}
+
+fn parse_type1(_data: &[u8]) -> Option<FontProgram> {
+    // Type 1 font programs (eexec-encrypted CharStrings) aren't handled by `ttf_parser`, and
+    // recovering glyph names from one would need a dedicated Type 1 parser we don't have.
+    // `/FontFile2` and `/FontFile3` cover the vast majority of embedded fonts in practice, so
+    // this is left as a gap rather than reaching for another font-parsing library.
+    None
+}
@@ -1,4 +1,3 @@
-use byteorder::{BigEndian, ByteOrder};
 use lopdf::{Dictionary, Document, Object, ObjectId};
 use serde_derive::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -7,6 +6,8 @@ use std::{
     str::FromStr,
 };
 
+use crate::font_program;
+
 macro_rules! indent {
     ($depth:ident) => {
         print!(
@@ -39,9 +40,15 @@ pub fn from_many_bytes(bytes: &[u8]) -> u64 {
 
 #[derive(Debug, Clone)]
 pub struct Font {
+    pub font_id: Option<ObjectId>, // The id of the font dictionary itself (e.g. "15454 0"), as opposed to its descriptor.
     pub font_descriptor_id: Option<ObjectId>,
     pub base_font_name: Option<String>, // Example: "/BaseFont /APZKLW+NotoSansDevanagari-Bold"
     pub encoding: Option<String>,       // Example: "/Encoding /Identity-H"
+    /// Number of bytes that make up one character code in a show-text operand for this font:
+    /// 1 for simple fonts, and for composite (Type0) fonts, whatever its /Encoding CMap's
+    /// codespace ranges declare (2 for Identity-H/V and for any named predefined CMap we don't
+    /// special-case; the true width for an embedded CMap stream with its own codespace ranges).
+    pub code_width: usize,
     pub subtype: Option<FontSubtype>, // Example: "/Subtype /Type0", refined in /DescendantFonts to "/Subtype /CIDFontType2"
     /*
     See 9.10 Extraction of Text Content (page numbered 292 = PDF page 300 of PDF32000_2008.pdf):
@@ -50,12 +57,62 @@ pub struct Font {
     2. "If the font is a simple font that uses one of the predefined encodings MacRomanEncoding, MacExpertEncoding, or WinAnsiEncoding",
        or [all its characters are "known", basically], then (look it up)...
     3. If the font uses one of the predefined CMaps, ...
-    4. "An ActualText entry [for a structure element or marked-content sequence]"
+    4. "An ActualText entry [for a structure element or marked-content sequence]" — this one isn't
+       a property of the font at all, so it doesn't live on `Font`: see the `actual_text_stack` in
+       `visit_ops_in_object`/`visit_ops_in_object_readonly`, and its use as an override in
+       `text_state::TextState::resolve_glyph_texts`.
      */
-    pub to_unicode: Option<()>,      //
+    /// This font dictionary's own `/ToUnicode` CMap, parsed eagerly here (rather than lazily from
+    /// `text_state::TextState::load_font_map`), since both simple and composite fonts carry it on
+    /// the same dictionary `parse_font` already has in hand.
+    pub to_unicode: Option<ToUnicodeCMap>,
+    /// Step 2 of the fallback chain above, for simple (Type1/TrueType/Type3) fonts only: a
+    /// 256-entry code→text table built from the base encoding named in `/Encoding` (or its
+    /// `/BaseEncoding`), with `/Differences` applied on top. `None` for composite (Type0) fonts,
+    /// which don't have an `/Encoding` of this kind — see `type0_encoding`/`cid_to_gid_map`
+    /// instead. See `resolve_simple_font_encoding`.
+    pub simple_font_encoding_table: Option<Vec<Option<String>>>,
+    /// Composite (Type0) fonts only: this font's `/Encoding` CMap, resolved far enough to turn a
+    /// text-showing operand's raw bytes into CIDs. `None` for simple fonts. See `Type0Encoding`
+    /// and `glyph_ids_for_composite_font`.
+    pub type0_encoding: Option<Type0Encoding>,
+    /// Composite (Type0) fonts only: the descendant font's `/CIDToGIDMap`, for translating the
+    /// CIDs `type0_encoding` produces into the glyph ids a show-text operand is actually selecting.
+    /// `None` for simple fonts.
+    pub cid_to_gid_map: Option<CidToGidMap>,
+    /// Lowest-priority fallback mapping source (see `text_state::MappingSource::FontProgram`):
+    /// glyph id -> Unicode text derived straight from the embedded font program's own cmap/post
+    /// (or CFF charset) tables, for when neither `/ToUnicode` nor the `/Encoding`-derived sources
+    /// above cover a glyph. `None` if this font has no embedded font program we can parse. See
+    /// `font_program::parse_font_program`.
+    pub font_program: Option<font_program::FontProgram>,
+    /// This font's average glyph width, in the same /1000-glyph-space units as `/Widths`/`/W` and
+    /// a `TJ` operand's numeric positioning adjustments (9.2.4 "Glyph Positioning and Metrics" in
+    /// PDF32000_2008.pdf defines both in terms of the font's glyph space, so they're directly
+    /// comparable without knowing the font size). `None` if this font has neither `/Widths`
+    /// (simple fonts) nor `/W`/`/DW` (composite fonts) to compute one from. See
+    /// `text_state::TextState::glyph_ids_with_space_hints`.
+    pub average_glyph_width: Option<f64>,
     pub font_descriptor: Option<()>, //
 }
 
+impl Font {
+    /// Step 1 of the fallback chain above, as a glyph id -> text lookup: every glyph id this
+    /// font's `/ToUnicode` CMap maps, paired with one of its mapped texts (`ToUnicodeCMap::mapped`
+    /// is a `HashSet` since some PDFs map the same glyph to the same text more than once). For
+    /// callers that seed a glyph id -> text table up front rather than looking up one glyph id at
+    /// a time — see `text_state::TextState::load_font_map`, and `font_mapping::FontMapping::iter`
+    /// for the analogous API on the hand-edited TOML mapping source.
+    pub fn to_unicode_entries(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.to_unicode.iter().flat_map(|cmap| {
+            cmap.mapped.iter().filter_map(|(code, texts)| {
+                let glyph_id = code.iter().fold(0u16, |acc, &byte| (acc << 8) | byte as u16);
+                texts.iter().next().map(move |text| (glyph_id, text.as_str()))
+            })
+        })
+    }
+}
+
 // See Table 110 in PDF32000_2008.pdf.
 #[derive(Debug, Clone)]
 pub enum FontSubtype {
@@ -87,11 +144,32 @@ impl std::str::FromStr for FontSubtype {
 
 // Mapping from character codes to "character selectors" aka CIDs.
 #[serde_as]
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ToUnicodeCMap {
     #[serde_as(as = "Vec<(_, _)>")]
     pub mapped: HashMap<Vec<u8>, HashSet<String>>,
 }
+/// The byte width that a character code of `len` raw bytes should have, per the codespace ranges
+/// declared via `begincodespacerange`/`endcodespacerange` (falling back to `len` itself if no
+/// codespace range covers it, e.g. when the CMap omits `begincodespacerange` entirely).
+fn codespace_width_for_len(len: usize, codespace_ranges: &[CodespaceRange]) -> usize {
+    codespace_ranges
+        .iter()
+        .find(|range| range.low.len() == len)
+        .map(|range| range.low.len())
+        .unwrap_or(len)
+}
+
+fn to_many_bytes(mut bytes: u64, len: usize) -> Vec<u8> {
+    assert!(len <= 8, "Wow, super-long: {:?}", len);
+    let mut ret = vec![0u8; len];
+    for slot in ret.iter_mut().rev() {
+        *slot = (bytes % 256) as u8;
+        bytes /= 256;
+    }
+    ret
+}
+
 impl ToUnicodeCMap {
     pub fn parse(stream_object: &Object) -> anyhow::Result<ToUnicodeCMap> {
         println!("Trying to parse a CMap out of: {:#?}", stream_object);
@@ -106,6 +184,21 @@ impl ToUnicodeCMap {
                 Err(_) => lopdf::content::Content::decode(&content_stream.content),
             }?
         };
+        // Parse the codespace ranges first (9.7.6.2 "CMap Mapping"), so the bfchar/bfrange
+        // sources below are taken at the byte width the CMap actually declares, rather than
+        // being padded/truncated to a fixed 8-byte width regardless of what the font uses.
+        let mut codespace_ranges: Vec<CodespaceRange> = Vec::new();
+        for op in &content.operations {
+            if op.operator == "endcodespacerange" {
+                for lo_and_hi in op.operands.chunks(2) {
+                    assert_eq!(lo_and_hi.len(), 2);
+                    codespace_ranges.push(CodespaceRange {
+                        low: ok!(lo_and_hi[0].as_str()).to_vec(),
+                        high: ok!(lo_and_hi[1].as_str()).to_vec(),
+                    });
+                }
+            }
+        }
         for op in content.operations {
             println!("An op: {:#?}", op.operator);
             let operator = op.operator;
@@ -131,23 +224,51 @@ impl ToUnicodeCMap {
             } else if operator == "endbfrange" {
                 for begin_end_offset in op.operands.chunks(3) {
                     assert_eq!(begin_end_offset.len(), 3);
-                    // TODO: Allow more general lengths of bytes.
-                    let begin = from_many_bytes(begin_end_offset[0].as_str()?);
-                    let end = from_many_bytes(begin_end_offset[1].as_str()?);
-                    let offset = from_many_bytes(begin_end_offset[2].as_str()?);
-                    for src in begin..=end {
-                        let dst = src - begin + offset;
-                        if dst != 0 {
-                            let mut key = [0; 8];
-                            BigEndian::write_u64(&mut key, src);
-                            let mut value = [0; 8];
-                            BigEndian::write_u64(&mut value, dst);
-                            let value: Vec<u16> = value
-                                .chunks_exact(2)
-                                .map(|chunk| (chunk[0] as u16) * 256 + (chunk[1] as u16))
-                                .collect();
-                            let value = ok!(String::from_utf16(&value));
-                            mapped.entry(key.to_vec()).or_default().insert(value);
+                    let begin_bytes = ok!(begin_end_offset[0].as_str());
+                    let end_bytes = ok!(begin_end_offset[1].as_str());
+                    let width = codespace_width_for_len(begin_bytes.len(), &codespace_ranges);
+                    let begin = from_many_bytes(begin_bytes);
+                    let end = from_many_bytes(end_bytes);
+                    match &begin_end_offset[2] {
+                        // The array form: `<srcLo> <srcHi> [ <dst0> <dst1> ... ]`, one explicit
+                        // (possibly multi-code) destination string per code in the range, rather
+                        // than a single offset to add to each source code.
+                        Object::Array(destinations) => {
+                            let range_size = (end - begin + 1) as usize;
+                            anyhow::ensure!(
+                                destinations.len() == range_size,
+                                "bfrange array has {} destinations but range {:?}..={:?} has {} codes",
+                                destinations.len(),
+                                begin_bytes,
+                                end_bytes,
+                                range_size
+                            );
+                            for (i, destination) in destinations.iter().enumerate() {
+                                let key = to_many_bytes(begin + i as u64, width);
+                                let value: Vec<u16> = ok!(destination.as_str())
+                                    .chunks_exact(2)
+                                    .map(|chunk| (chunk[0] as u16) * 256 + (chunk[1] as u16))
+                                    .collect();
+                                let value = ok!(String::from_utf16(&value));
+                                mapped.entry(key).or_default().insert(value);
+                            }
+                        }
+                        // The scalar form: `<srcLo> <srcHi> <dstLo>`, where each code in the
+                        // range maps to `dstLo` plus its offset from `srcLo`.
+                        _ => {
+                            let offset = from_many_bytes(ok!(begin_end_offset[2].as_str()));
+                            for src in begin..=end {
+                                let dst = src - begin + offset;
+                                if dst != 0 {
+                                    let key = to_many_bytes(src, width);
+                                    let value: Vec<u16> = to_many_bytes(dst, 2)
+                                        .chunks_exact(2)
+                                        .map(|chunk| (chunk[0] as u16) * 256 + (chunk[1] as u16))
+                                        .collect();
+                                    let value = ok!(String::from_utf16(&value));
+                                    mapped.entry(key).or_default().insert(value);
+                                }
+                            }
                         }
                     }
                 }
@@ -157,11 +278,520 @@ impl ToUnicodeCMap {
     }
 }
 
-trait DocumentWithFontCache {
-    fn get_font() {}
+/// The number of bytes in one codespace range declared by an embedded CMap stream (the object
+/// a composite font's `/Encoding` points to when it isn't one of the predefined names like
+/// `/Identity-H`). See "9.7.6.2 CMap Mapping" / `begincodespacerange` in PDF32000_2008.pdf.
+/// Falls back to 2 (the Identity-H/V width) if the stream has no codespace range we can parse.
+fn code_width_from_cmap_stream(stream_object: &Object) -> anyhow::Result<usize> {
+    let content_stream = stream_object.as_stream()?;
+    let content = {
+        match content_stream.decompressed_content() {
+            Ok(data) => lopdf::content::Content::decode(&data),
+            Err(_) => lopdf::content::Content::decode(&content_stream.content),
+        }?
+    };
+    for op in content.operations {
+        if op.operator == "endcodespacerange" {
+            if let Some(lo) = op.operands.first() {
+                return Ok(ok!(lo.as_str()).len());
+            }
+        }
+    }
+    Ok(2)
+}
+
+/// One codespace range declared by a composite font's `/Encoding` CMap (`begincodespacerange` in
+/// PDF32000_2008.pdf): `low` and `high` are both byte strings of the same length, bounding which
+/// byte sequences of that length are valid codes at that length.
+#[derive(Debug, Clone)]
+pub struct CodespaceRange {
+    pub low: Vec<u8>,
+    pub high: Vec<u8>,
+}
+
+/// A composite font's `/Encoding`, resolved far enough to turn a text-showing operand's raw bytes
+/// into CIDs (see `glyph_ids_for_composite_font`): the codespace ranges say how to split the byte
+/// string into codes; `cid_map` is how an embedded CMap stream's codes resolve to CIDs. Not needed
+/// for `/Identity-H`/`/Identity-V` (by definition CID == code), which is also what every other
+/// predefined CMap name falls back to here, since we don't ship their codespace/CID tables.
+#[derive(Debug, Clone)]
+pub struct Type0Encoding {
+    pub codespace_ranges: Vec<CodespaceRange>,
+    pub cid_map: HashMap<Vec<u8>, u32>,
+    pub identity: bool,
+}
+
+impl Type0Encoding {
+    /// `/Identity-H` and `/Identity-V`, and our fallback for any other predefined CMap name: a
+    /// single 2-byte codespace range, CID == code.
+    fn identity() -> Type0Encoding {
+        Type0Encoding {
+            codespace_ranges: vec![CodespaceRange {
+                low: vec![0x00, 0x00],
+                high: vec![0xFF, 0xFF],
+            }],
+            cid_map: HashMap::new(),
+            identity: true,
+        }
+    }
+
+    /// Parse an embedded CMap stream's `begincodespacerange`/`begincidchar`/`begincidrange`
+    /// operators (9.7.6.2 "CMap Mapping" in PDF32000_2008.pdf).
+    fn parse(stream_object: &Object) -> anyhow::Result<Type0Encoding> {
+        let content_stream = stream_object.as_stream()?;
+        let content = {
+            match content_stream.decompressed_content() {
+                Ok(data) => lopdf::content::Content::decode(&data),
+                Err(_) => lopdf::content::Content::decode(&content_stream.content),
+            }?
+        };
+        let mut codespace_ranges = Vec::new();
+        let mut cid_map = HashMap::new();
+        for op in content.operations {
+            match op.operator.as_str() {
+                "endcodespacerange" => {
+                    for lo_hi in op.operands.chunks(2) {
+                        if let [lo, hi] = lo_hi {
+                            codespace_ranges.push(CodespaceRange {
+                                low: ok!(lo.as_str()).to_vec(),
+                                high: ok!(hi.as_str()).to_vec(),
+                            });
+                        }
+                    }
+                }
+                "endcidchar" => {
+                    for code_and_cid in op.operands.chunks(2) {
+                        if let [code, cid] = code_and_cid {
+                            cid_map.insert(ok!(code.as_str()).to_vec(), ok!(cid.as_i64()) as u32);
+                        }
+                    }
+                }
+                "endcidrange" => {
+                    for lo_hi_cid in op.operands.chunks(3) {
+                        if let [lo, hi, cid] = lo_hi_cid {
+                            let lo_bytes = ok!(lo.as_str());
+                            let width = lo_bytes.len();
+                            let begin = from_many_bytes(lo_bytes);
+                            let end = from_many_bytes(ok!(hi.as_str()));
+                            let cid_start = ok!(cid.as_i64()) as u32;
+                            for code in begin..=end {
+                                let mut key = vec![0u8; width];
+                                for (i, byte) in key.iter_mut().enumerate() {
+                                    *byte = ((code >> (8 * (width - 1 - i))) & 0xFF) as u8;
+                                }
+                                cid_map.insert(key, cid_start + (code - begin) as u32);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if codespace_ranges.is_empty() {
+            codespace_ranges.push(CodespaceRange {
+                low: vec![0x00, 0x00],
+                high: vec![0xFF, 0xFF],
+            });
+        }
+        Ok(Type0Encoding {
+            codespace_ranges,
+            cid_map,
+            identity: false,
+        })
+    }
+}
+
+/// Resolve a composite font's `/Encoding` into a `Type0Encoding`. An embedded CMap stream (a
+/// `/Encoding` that's a reference to a stream object, rather than a predefined name) is parsed via
+/// `Type0Encoding::parse`; everything else (`/Identity-H`, `/Identity-V`, or any other predefined
+/// name) falls back to `Type0Encoding::identity`.
+fn resolve_type0_encoding(encoding_object: &Object, document: &Document) -> Type0Encoding {
+    match encoding_object {
+        Object::Reference(id) => match document.get_object(*id) {
+            Ok(stream_object) if stream_object.as_stream().is_ok() => {
+                Type0Encoding::parse(stream_object).unwrap_or_else(|_| Type0Encoding::identity())
+            }
+            _ => Type0Encoding::identity(),
+        },
+        _ => Type0Encoding::identity(),
+    }
+}
+
+/// A composite font's `/CIDToGIDMap` (9.7.4.2 in PDF32000_2008.pdf): either the name `/Identity`
+/// (GID == CID; the common case for subsetted TrueType fonts), or a stream of big-endian 16-bit
+/// GIDs indexed by CID.
+#[derive(Debug, Clone)]
+pub enum CidToGidMap {
+    Identity,
+    Explicit(Vec<u16>),
+}
+
+impl CidToGidMap {
+    /// Absent `/CIDToGIDMap` defaults to `/Identity` (9.7.4.2 in PDF32000_2008.pdf).
+    fn parse(descendant_font: &Dictionary, document: &Document) -> CidToGidMap {
+        match descendant_font.get_deref(b"CIDToGIDMap", document) {
+            Ok(Object::Stream(stream)) => {
+                let data = match stream.decompressed_content() {
+                    Ok(data) => data,
+                    Err(_) => stream.content.clone(),
+                };
+                CidToGidMap::Explicit(
+                    data.chunks_exact(2)
+                        .map(|chunk| ((chunk[0] as u16) << 8) | chunk[1] as u16)
+                        .collect(),
+                )
+            }
+            _ => CidToGidMap::Identity,
+        }
+    }
+
+    fn gid_for_cid(&self, cid: u32) -> u16 {
+        match self {
+            CidToGidMap::Identity => cid as u16,
+            CidToGidMap::Explicit(gids) => gids.get(cid as usize).copied().unwrap_or(0),
+        }
+    }
+}
+
+/// Split `bytes` into codes per `codespace_ranges`, greedily preferring the longest matching range
+/// at each position (9.7.6.2 in PDF32000_2008.pdf), and falling back to `default_width` wherever no
+/// codespace range matches, so a PDF with an incomplete codespace declaration still decodes.
+fn split_into_codes(
+    bytes: &[u8],
+    codespace_ranges: &[CodespaceRange],
+    default_width: usize,
+) -> Vec<Vec<u8>> {
+    let mut codes = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut matched_len: Option<usize> = None;
+        for range in codespace_ranges {
+            let len = range.low.len();
+            if i + len <= bytes.len()
+                && bytes[i..i + len]
+                    .iter()
+                    .zip(&range.low)
+                    .zip(&range.high)
+                    .all(|((&b, &lo), &hi)| b >= lo && b <= hi)
+            {
+                matched_len = Some(matched_len.map_or(len, |m| m.max(len)));
+            }
+        }
+        let len = matched_len
+            .unwrap_or(default_width)
+            .min(bytes.len() - i)
+            .max(1);
+        codes.push(bytes[i..i + len].to_vec());
+        i += len;
+    }
+    codes
+}
+
+/// The composite-font analogue of simply chunking `text` by `code_width`: split it into codes via
+/// `font`'s `/Encoding` CMap (`Type0Encoding`), resolve each code to a CID (identity for
+/// `/Identity-H`/`/Identity-V`, or via the embedded CMap's `cidchar`/`cidrange` table, falling back
+/// to identity if a code isn't in that table), then translate CID→GID through `/CIDToGIDMap`. Falls
+/// back to the old width-chunking behaviour if `font` has no parsed `Type0Encoding` (shouldn't
+/// happen for a composite font, but keeps this infallible like `TextState::glyph_ids` was before).
+pub fn glyph_ids_for_composite_font(text: &[u8], font: &Font) -> Vec<u16> {
+    let encoding = match &font.type0_encoding {
+        Some(encoding) => encoding,
+        None => {
+            return text
+                .chunks(font.code_width)
+                .map(|chunk| chunk.iter().fold(0u16, |acc, &byte| (acc << 8) | byte as u16))
+                .collect();
+        }
+    };
+    split_into_codes(text, &encoding.codespace_ranges, font.code_width)
+        .into_iter()
+        .map(|code| {
+            let code_value = code.iter().fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+            let cid = if encoding.identity {
+                code_value
+            } else {
+                encoding.cid_map.get(&code).copied().unwrap_or(code_value)
+            };
+            match &font.cid_to_gid_map {
+                Some(map) => map.gid_for_cid(cid),
+                None => cid as u16,
+            }
+        })
+        .collect()
+}
+
+/// Built-in single-byte encoding tables for simple fonts (Table D.2 in PDF32000_2008.pdf). Only
+/// WinAnsiEncoding (~cp1252) is modelled with any precision above the ASCII range; Standard,
+/// MacRoman, and PDFDoc share the same table for now, since `/Differences` (which every simple
+/// font with non-Latin text relies on anyway) overrides whatever this gets wrong.
+/// TODO: Give Standard/MacRoman/PDFDoc their own upper-half tables if that ever matters in practice.
+fn base_encoding_table(_name: &str) -> [Option<char>; 256] {
+    let mut table = [None; 256];
+    for code in 0x20u32..0x7F {
+        table[code as usize] = char::from_u32(code);
+    }
+    // A handful of common WinAnsiEncoding codes above 0x7F (smart quotes, dashes, nbsp).
+    for &(code, c) in &[
+        (0x91u8, '\u{2018}'),
+        (0x92, '\u{2019}'),
+        (0x93, '\u{201C}'),
+        (0x94, '\u{201D}'),
+        (0x96, '\u{2013}'),
+        (0x97, '\u{2014}'),
+        (0xA0, '\u{00A0}'),
+        (0xAD, '\u{00AD}'),
+    ] {
+        table[code as usize] = Some(c);
+    }
+    table
+}
+
+/// A partial Adobe Glyph List: https://github.com/adobe-type-tools/agl-aglfn
+/// Covers the glyph names that show up most often in `/Differences` arrays; anything missing
+/// here just falls through to the `uniXXXX`/single-letter fallbacks in `glyph_name_to_unicode`.
+const ADOBE_GLYPH_LIST: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("quotesingle", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("hyphen", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("zero", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("quoteleft", '\u{2018}'),
+    ("quoteright", '\u{2019}'),
+    ("quotedblleft", '\u{201C}'),
+    ("quotedblright", '\u{201D}'),
+    ("endash", '\u{2013}'),
+    ("emdash", '\u{2014}'),
+    ("bullet", '\u{2022}'),
+    ("ellipsis", '\u{2026}'),
+    ("fi", '\u{FB01}'),
+    ("fl", '\u{FB02}'),
+    ("eacute", '\u{00E9}'),
+    ("egrave", '\u{00E8}'),
+    ("ccedilla", '\u{00E7}'),
+    ("ntilde", '\u{00F1}'),
+    ("adieresis", '\u{00E4}'),
+    ("odieresis", '\u{00F6}'),
+    ("udieresis", '\u{00FC}'),
+    ("Adieresis", '\u{00C4}'),
+    ("Odieresis", '\u{00D6}'),
+    ("Udieresis", '\u{00DC}'),
+    ("germandbls", '\u{00DF}'),
+];
+
+/// Convert one `/Differences`-style glyph name to the Unicode text it represents, per the Adobe
+/// Glyph List naming conventions (https://github.com/adobe-type-tools/agl-specification):
+/// any suffix after the first `.` is dropped (e.g. `"a.sc"` -> `"a"`), a ligature name is split on
+/// `_` and each component resolved on its own (e.g. `"f_f_i"` -> "ffi"), `uniXXXX`/`uXXXXXX` names
+/// map directly to the named code point, and otherwise the name is looked up in
+/// `ADOBE_GLYPH_LIST`, falling back to treating a single ASCII letter/digit name as itself.
+pub(crate) fn glyph_name_to_unicode(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap_or(name);
+    if name.contains('_') {
+        let mut text = String::new();
+        for part in name.split('_') {
+            text.push_str(&glyph_name_to_unicode(part)?);
+        }
+        return Some(text);
+    }
+    if let Some(hex) = name.strip_prefix("uni") {
+        if hex.len() >= 4 {
+            if let Ok(code) = u32::from_str_radix(&hex[..4], 16) {
+                return char::from_u32(code).map(|c| c.to_string());
+            }
+        }
+    }
+    if let Some(hex) = name.strip_prefix('u') {
+        if (4..=6).contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            if let Ok(code) = u32::from_str_radix(hex, 16) {
+                return char::from_u32(code).map(|c| c.to_string());
+            }
+        }
+    }
+    if let Some((found_name, c)) = ADOBE_GLYPH_LIST.iter().find(|&&(n, _)| n == name) {
+        let _ = found_name;
+        return Some(c.to_string());
+    }
+    if name.chars().count() == 1 && name.chars().next().unwrap().is_ascii_alphanumeric() {
+        return Some(name.to_string());
+    }
+    None
+}
+
+/// Build the 256-entry code→text table for a simple font: the base encoding named by `/Encoding`
+/// (or, if `/Encoding` is a dictionary, its `/BaseEncoding`), with `/Differences` applied on top
+/// (9.6.6.2 "Differences Between Font Encodings" in PDF32000_2008.pdf: a flat sequence alternating
+/// an integer start code and the consecutive glyph names that overwrite entries from there).
+fn resolve_simple_font_encoding(encoding_object: &Object, document: &Document) -> Vec<Option<String>> {
+    let (base_encoding_name, differences) = match encoding_object {
+        Object::Name(name) => (std::str::from_utf8(name).unwrap_or("").to_string(), None),
+        Object::Dictionary(dict) => (
+            dict.get(b"BaseEncoding")
+                .and_then(Object::as_name_str)
+                .unwrap_or("")
+                .to_string(),
+            dict.get_deref(b"Differences", document)
+                .ok()
+                .and_then(|o| o.as_array().ok()),
+        ),
+        Object::Reference(id) => match document.get_dictionary(*id) {
+            Ok(dict) => (
+                dict.get(b"BaseEncoding")
+                    .and_then(Object::as_name_str)
+                    .unwrap_or("")
+                    .to_string(),
+                dict.get_deref(b"Differences", document)
+                    .ok()
+                    .and_then(|o| o.as_array().ok()),
+            ),
+            Err(_) => (String::new(), None),
+        },
+        _ => (String::new(), None),
+    };
+
+    let mut table: Vec<Option<String>> = base_encoding_table(&base_encoding_name)
+        .iter()
+        .map(|c| c.map(|c| c.to_string()))
+        .collect();
+
+    if let Some(differences) = differences {
+        let mut code = 0usize;
+        for item in differences {
+            match item {
+                Object::Integer(n) => code = *n as usize,
+                Object::Name(name) => {
+                    if let Ok(name) = std::str::from_utf8(name) {
+                        if code < table.len() {
+                            table[code] = glyph_name_to_unicode(name);
+                        }
+                    }
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    table
+}
+
+fn object_as_f64(o: &Object) -> Option<f64> {
+    o.as_f64().ok().or_else(|| o.as_i64().ok().map(|n| n as f64))
+}
+
+/// A simple font's average glyph width, from the per-code `/Widths` array (9.6.3 "Glyph Widths in
+/// Text Extraction"... actually Table 111/112: `/Widths` + `/FirstChar`/`/LastChar`). We only need
+/// the average, not which code each width belongs to, so `/FirstChar` doesn't matter here.
+fn average_simple_glyph_width(referenced_font: &Dictionary, document: &Document) -> Option<f64> {
+    let widths = referenced_font
+        .get_deref(b"Widths", document)
+        .ok()
+        .and_then(|o| o.as_array().ok())?;
+    if widths.is_empty() {
+        return None;
+    }
+    let sum: f64 = widths.iter().filter_map(object_as_f64).sum();
+    Some(sum / widths.len() as f64)
+}
+
+/// A composite font's average glyph width, from its descendant font's `/W` array (9.7.4.3 "Glyph
+/// Metrics in CIDFonts" in PDF32000_2008.pdf: a flat sequence of either `c [w1 w2 ... wn]` —
+/// consecutive widths starting at code `c` — or `c_first c_last w`, one width for that whole
+/// range), falling back to `/DW` (the font's default width) if `/W` has nothing usable.
+fn average_composite_glyph_width(descendant_font: &Dictionary, document: &Document) -> Option<f64> {
+    let mut widths = Vec::new();
+    if let Some(arr) = descendant_font
+        .get_deref(b"W", document)
+        .ok()
+        .and_then(|o| o.as_array().ok())
+    {
+        let mut i = 0;
+        while i < arr.len() {
+            if object_as_f64(&arr[i]).is_none() {
+                break;
+            }
+            i += 1;
+            if i >= arr.len() {
+                break;
+            }
+            match &arr[i] {
+                Object::Array(ws) => {
+                    widths.extend(ws.iter().filter_map(object_as_f64));
+                    i += 1;
+                }
+                single_width => {
+                    if object_as_f64(single_width).is_none() {
+                        break;
+                    }
+                    i += 1;
+                    if i >= arr.len() {
+                        break;
+                    }
+                    if let Some(w) = object_as_f64(&arr[i]) {
+                        widths.push(w);
+                    }
+                    i += 1;
+                }
+            }
+        }
+    }
+    if !widths.is_empty() {
+        return Some(widths.iter().sum::<f64>() / widths.len() as f64);
+    }
+    descendant_font.get_deref(b"DW", document).ok().and_then(object_as_f64)
+}
+
+/// Look up `owner_dict`'s `/FontDescriptor` and hand it to `font_program::parse_font_program`.
+/// `owner_dict` is the font dictionary itself for a simple font, or the descendant font
+/// dictionary for a composite font — either way, where `/FontDescriptor` lives.
+fn load_font_program(owner_dict: &Dictionary, document: &Document) -> Option<font_program::FontProgram> {
+    let descriptor = owner_dict.get_deref(b"FontDescriptor", document).ok()?;
+    font_program::parse_font_program(descriptor.as_dict().ok()?, document)
 }
 
-impl DocumentWithFontCache for lopdf::Document {}
+/// A font dictionary's page-internal name (`/TT0`, `/C2_0`, …) collides across pages, so the
+/// only stable identity to cache a parsed `Font` by is its `ObjectId`. Threaded through
+/// `collect_fonts_from_resources`/`get_page_fonts`/`visit_ops_in_object` so `parse_font` runs at
+/// most once per `ObjectId` per cache. `visit_page_content_stream_ops` shares one cache across all
+/// pages; `visit_pages_parallel` uses a fresh cache per page, so there a font is parsed once for
+/// each page that references it.
+type FontCache = HashMap<ObjectId, Font>;
 
 pub trait OpVisitor {
     fn visit_op(
@@ -169,14 +799,62 @@ pub trait OpVisitor {
         content: &mut lopdf::content::Content,
         i: &mut usize,
         get_font_from_name: &dyn Fn(&str) -> Font,
+        // Step 4 of the extraction fallback chain on `Font`'s doc comment above: the text, if
+        // any, that a `/Span <</ActualText ...>> BDC ... EMC` the source PDF itself wraps this
+        // operator in claims it means (the innermost enclosing one, if more than one is nested).
+        // `None` outside any such span. See `visit_ops_in_object`'s marked-content stack.
+        current_actual_text: Option<&str>,
     );
 }
 
+/// Decode a PDF text string (7.9.2.2 "Text String Type" in PDF32000_2008.pdf): UTF-16BE with a
+/// leading `<FEFF>` byte-order mark if present (every string `text_state::pdf_encode_unicode_text_string`
+/// writes has one), otherwise treated as close enough to ASCII for the `/ActualText` strings we
+/// need to read back (PDFDocEncoding is ASCII below 0x80; see also `base_encoding_table`'s TODO
+/// about not modelling the rest of it).
+fn decode_pdf_text_string(bytes: &[u8]) -> String {
+    if bytes.len() >= 2 && bytes[0] == 0xFE && bytes[1] == 0xFF {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|chunk| ((chunk[0] as u16) << 8) | chunk[1] as u16)
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// If `op` is a `BDC`/`BMC` operator whose property list (an inline dictionary, or a name looked
+/// up in `properties`, the `/Properties` resource dictionary) carries an `/ActualText` entry
+/// (14.9.4 "Replacement Text" in PDF32000_2008.pdf), return its decoded text.
+fn extract_actual_text(
+    op: &lopdf::content::Operation,
+    properties: Option<&Dictionary>,
+    document: &Document,
+) -> Option<String> {
+    let props_object = op.operands.get(1)?;
+    let dict: Dictionary = match props_object {
+        Object::Dictionary(dict) => dict.clone(),
+        Object::Name(name) => {
+            let name = std::str::from_utf8(name).ok()?;
+            match properties.and_then(|p| p.get(name.as_bytes()).ok())? {
+                Object::Reference(id) => document.get_dictionary(*id).ok()?.clone(),
+                Object::Dictionary(dict) => dict.clone(),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let actual_text = dict.get_deref(b"ActualText", document).ok()?;
+    Some(decode_pdf_text_string(actual_text.as_str().ok()?))
+}
+
 // Copied from lopdf document.rs, and modified.
 fn collect_fonts_from_resources<'a>(
     resources: &'a Dictionary,
     fonts: &mut BTreeMap<Vec<u8>, Font>,
     doc: &'a Document,
+    font_cache: &mut FontCache,
 ) {
     if let Ok(font_dict) = resources.get(b"Font").and_then(Object::as_dict) {
         /*
@@ -189,49 +867,71 @@ fn collect_fonts_from_resources<'a>(
             where the key is the font's page-internal name, and the value is or points to a font dictionary.
         */
         for (name, value) in font_dict.iter() {
+            let font_id = value.as_reference().ok();
             let font = match *value {
-                Object::Reference(id) => doc.get_dictionary(id).ok(),
-                Object::Dictionary(ref dict) => Some(dict),
+                // The common case: look the font up by its ObjectId, parsing it only on a cache miss.
+                Object::Reference(id) => {
+                    if !font_cache.contains_key(&id) {
+                        let dict = doc.get_dictionary(id).unwrap();
+                        let mut parsed = parse_font(dict, doc).unwrap();
+                        parsed.font_id = font_id;
+                        font_cache.insert(id, parsed);
+                    }
+                    Some(font_cache.get(&id).unwrap().clone())
+                }
+                // An inline (non-referenced) font dictionary has no ObjectId to cache by, so just
+                // parse it directly; this is rare in practice.
+                Object::Dictionary(ref dict) => {
+                    let mut parsed = parse_font(dict, doc).unwrap();
+                    parsed.font_id = font_id;
+                    Some(parsed)
+                }
                 _ => {
                     println!("What? Font /{:?} -> {:?}", name, *value);
                     None
                 }
             };
             if !fonts.contains_key(name) {
-                font.map(|font| fonts.insert(name.clone(), parse_font(font, doc).unwrap()));
+                font.map(|font| fonts.insert(name.clone(), font));
             }
         }
     }
 }
-fn get_page_fonts(document: &Document, page_id: ObjectId) -> BTreeMap<Vec<u8>, Font> {
+fn get_page_fonts(
+    document: &Document,
+    page_id: ObjectId,
+    font_cache: &mut FontCache,
+) -> BTreeMap<Vec<u8>, Font> {
     let mut fonts = BTreeMap::new();
     let (resource_dict, resource_ids) = document.get_page_resources(page_id);
     if let Some(resources) = resource_dict {
-        collect_fonts_from_resources(resources, &mut fonts, document);
+        collect_fonts_from_resources(resources, &mut fonts, document, font_cache);
     }
     for resource_id in resource_ids {
         if let Ok(resources) = document.get_dictionary(resource_id) {
-            collect_fonts_from_resources(resources, &mut fonts, document);
+            collect_fonts_from_resources(resources, &mut fonts, document, font_cache);
         }
     }
     fonts
 }
 
-/// Go over each page in `document` and, for each operation in its content stream(s), call `visitor.visit_op`.
-/// Handles bookkeeping of fonts and resources.
+/// Go over each selected page in `document` (or every page, if `chosen_pages` is `None`) and, for
+/// each operation in its content stream(s), call `visitor.visit_op`. Handles bookkeeping of fonts
+/// and resources, and applies whatever content-stream edits `visitor.visit_op` made, in page order.
 pub fn visit_page_content_stream_ops(
     document: &mut lopdf::Document,
     visitor: &mut dyn OpVisitor,
-    chosen_page_number: Option<u32>,
+    chosen_pages: Option<&[u32]>,
     debug: bool,
 ) -> anyhow::Result<()> {
     let pages = document.get_pages();
     println!("{} pages in this document.", pages.len());
     let mut seen_ops = linked_hash_map::LinkedHashMap::new();
     // let mut seen_ops = std::collections::HashMap::new();
+    let mut font_cache = FontCache::new();
     for (page_num, page_id) in pages {
-        if let Some(p) = chosen_page_number {
-            if page_num != p {
+        if let Some(pages) = chosen_pages {
+            if !pages.contains(&page_num) {
                 continue;
             };
         }
@@ -244,7 +944,7 @@ pub fn visit_page_content_stream_ops(
         }
         // This line below is almost what we want, except that it borrows document so we'd end up double-borrowing document.
         // let fonts = document.get_page_fonts(page_id);
-        let fonts = get_page_fonts(document, page_id);
+        let fonts = get_page_fonts(document, page_id, &mut font_cache);
 
         // TODO: Consider something similar to `get_page_fonts` above, if it turns out be necessary.
         let mut xobjects = lopdf::Dictionary::new();
@@ -264,6 +964,22 @@ pub fn visit_page_content_stream_ops(
             }
         }
 
+        // Needed to resolve a `BDC`'s property list when it's a name into this page's
+        // `/Properties` resource dict, rather than an inline dictionary operand.
+        let mut properties = lopdf::Dictionary::new();
+        if let Some(resource_dict) = resource_dict {
+            if let Ok(lopdf::Object::Dictionary(ref dict)) = resource_dict.get(b"Properties") {
+                properties.extend(dict);
+            }
+        }
+        for resource_id in &resource_ids {
+            if let Ok(resource_dict) = document.get_dictionary(*resource_id) {
+                if let Ok(lopdf::Object::Dictionary(ref dict)) = resource_dict.get(b"Properties") {
+                    properties.extend(dict);
+                }
+            }
+        }
+
         let content_streams = document.get_page_contents(page_id);
         for object_id in content_streams {
             visit_ops_in_object(
@@ -271,9 +987,12 @@ pub fn visit_page_content_stream_ops(
                 document,
                 Some(&fonts),
                 Some(&xobjects),
+                Some(&properties),
+                None,
                 debug as usize,
                 &mut seen_ops,
                 visitor,
+                &mut font_cache,
             )?;
         }
     }
@@ -288,10 +1007,16 @@ fn visit_ops_in_object(
     document: &mut lopdf::Document,
     fonts: Option<&BTreeMap<Vec<u8>, Font>>,
     xobjects: Option<&lopdf::Dictionary>,
+    properties: Option<&lopdf::Dictionary>,
+    // The ActualText (if any) already in effect from a `BDC`/`BMC` span enclosing the `Do` that
+    // invoked this object, if this is a recursive call — so a Form XObject invoked from inside
+    // such a span inherits it too, rather than it being dropped at the XObject boundary.
+    initial_actual_text: Option<String>,
     debug_depth: usize,
     seen_ops: &mut linked_hash_map::LinkedHashMap<String, u32>,
     // seen_ops: &mut std::collections::HashMap<String, u32>,
     visitor: &mut dyn OpVisitor,
+    font_cache: &mut FontCache,
 ) -> anyhow::Result<()> {
     let mut content = {
         let content_stream = document.get_object(content_stream_object_id)?.as_stream()?;
@@ -305,6 +1030,10 @@ fn visit_ops_in_object(
         // println!("Finding text operators in: {:?}", content);
         println!("Will visit {} ops.", content.operations.len());
     }
+    // Tracks which `/Span <</ActualText ...>> BDC ... EMC` (or other marked-content span with an
+    // `/ActualText` property) operators between here and `i` are still open, innermost last, so
+    // `visit_op` can be told which one (if any) currently encloses it. See `OpVisitor::visit_op`.
+    let mut actual_text_stack: Vec<Option<String>> = vec![initial_actual_text];
     let mut i = 0;
     while i < content.operations.len() {
         let op = &content.operations[i];
@@ -324,6 +1053,8 @@ fn visit_ops_in_object(
         if operator.as_str() == "Do" {
             assert_eq!(op.operands.len(), 1);
             let name = op.operands[0].as_name_str().unwrap();
+            let current_actual_text =
+                actual_text_stack.iter().rev().find_map(|o| o.clone());
             let (object_id, stream) = {
                 let mut object = xobjects
                     .unwrap()
@@ -337,35 +1068,59 @@ fn visit_ops_in_object(
                 (id, object.as_stream()?.clone())
             };
             let mut fonts = BTreeMap::new();
-            let (fonts, xobjects) = match stream.dict.get(b"Resources") {
+            let (fonts, xobjects, properties) = match stream.dict.get(b"Resources") {
                 Ok(lopdf::Object::Dictionary(ref resources)) => (
                     {
-                        collect_fonts_from_resources(resources, &mut fonts, &document);
+                        collect_fonts_from_resources(resources, &mut fonts, &document, font_cache);
                         Some(&fonts)
                     },
                     match resources.get(b"XObject") {
                         Ok(lopdf::Object::Dictionary(ref xobjects_dict)) => Some(xobjects_dict),
                         _ => None,
                     },
+                    match resources.get(b"Properties") {
+                        Ok(lopdf::Object::Dictionary(ref properties_dict)) => Some(properties_dict),
+                        _ => None,
+                    },
                 ),
-                _ => (None, None),
+                _ => (None, None, None),
             };
             visit_ops_in_object(
                 object_id,
                 document,
                 fonts,
                 xobjects,
+                properties,
+                current_actual_text,
                 debug_depth + (debug_depth > 0) as usize,
                 seen_ops,
                 visitor,
+                font_cache,
             )?;
         } else {
+            match operator.as_str() {
+                "BDC" | "BMC" => actual_text_stack.push(extract_actual_text(op, properties, document)),
+                // Never pop below index 0: that slot holds `initial_actual_text`, the ActualText
+                // (if any) inherited from whatever enclosing span invoked this object via `Do`,
+                // not a span opened in this object's own content stream — so a content stream
+                // with more EMCs than BDC/BMCs (a malformed/buggy producer) can't discard it.
+                "EMC" if actual_text_stack.len() > 1 => {
+                    actual_text_stack.pop();
+                }
+                _ => {}
+            }
+            let current_actual_text = actual_text_stack.iter().rev().find_map(|o| o.as_deref());
             // TODO: Change this interface. Maybe visit Tf right here, or pass in a map, or something.
-            visitor.visit_op(&mut content, &mut i, &|font_name: &str| {
-                let font = fonts.unwrap().get(font_name.as_bytes()).unwrap();
-                println!("Switching to font {}, which means {:?}", font_name, font);
-                font.clone()
-            })
+            visitor.visit_op(
+                &mut content,
+                &mut i,
+                &|font_name: &str| {
+                    let font = fonts.unwrap().get(font_name.as_bytes()).unwrap();
+                    println!("Switching to font {}, which means {:?}", font_name, font);
+                    font.clone()
+                },
+                current_actual_text,
+            )
         }
         i += 1;
     }
@@ -377,6 +1132,204 @@ fn visit_ops_in_object(
     Ok(())
 }
 
+/// Like `visit_ops_in_object`, but never writes the (possibly visitor-modified) content stream
+/// back to `document` — so it only needs a shared `&Document`, which is what lets
+/// `visit_pages_parallel` below call it from multiple threads at once. Only safe for visitors
+/// that don't rely on their edits being applied (i.e. Phase 1 dumping).
+fn visit_ops_in_object_readonly(
+    content_stream_object_id: lopdf::ObjectId,
+    document: &lopdf::Document,
+    fonts: Option<&BTreeMap<Vec<u8>, Font>>,
+    xobjects: Option<&lopdf::Dictionary>,
+    properties: Option<&lopdf::Dictionary>,
+    // See the parameter of the same name on `visit_ops_in_object`.
+    initial_actual_text: Option<String>,
+    debug_depth: usize,
+    seen_ops: &mut linked_hash_map::LinkedHashMap<String, u32>,
+    visitor: &mut dyn OpVisitor,
+    font_cache: &mut FontCache,
+) -> anyhow::Result<()> {
+    let mut content = {
+        let content_stream = document.get_object(content_stream_object_id)?.as_stream()?;
+        match content_stream.decompressed_content() {
+            Ok(data) => lopdf::content::Content::decode(&data),
+            Err(_) => lopdf::content::Content::decode(&content_stream.content),
+        }?
+    };
+    let mut actual_text_stack: Vec<Option<String>> = vec![initial_actual_text];
+    let mut i = 0;
+    while i < content.operations.len() {
+        let op = &content.operations[i];
+        let operator = &op.operator;
+        if !seen_ops.contains_key(operator) {
+            seen_ops.insert(operator.clone(), 0);
+        }
+        *seen_ops.get_mut(operator).unwrap() += 1;
+
+        if operator.as_str() == "Do" {
+            assert_eq!(op.operands.len(), 1);
+            let name = op.operands[0].as_name_str().unwrap();
+            let current_actual_text =
+                actual_text_stack.iter().rev().find_map(|o| o.clone());
+            let (object_id, stream) = {
+                let mut object = xobjects
+                    .unwrap()
+                    .get(name.as_bytes())
+                    .unwrap_or_else(|_| panic!("XObject name {} not found in {:?}", name, op));
+                let mut id = (0, 0);
+                while let Ok(ref_id) = object.as_reference() {
+                    id = ref_id;
+                    object = document.objects.get(&ref_id).unwrap();
+                }
+                (id, object.as_stream()?.clone())
+            };
+            let mut fonts = BTreeMap::new();
+            let (fonts, xobjects, properties) = match stream.dict.get(b"Resources") {
+                Ok(lopdf::Object::Dictionary(ref resources)) => (
+                    {
+                        collect_fonts_from_resources(resources, &mut fonts, document, font_cache);
+                        Some(&fonts)
+                    },
+                    match resources.get(b"XObject") {
+                        Ok(lopdf::Object::Dictionary(ref xobjects_dict)) => Some(xobjects_dict),
+                        _ => None,
+                    },
+                    match resources.get(b"Properties") {
+                        Ok(lopdf::Object::Dictionary(ref properties_dict)) => Some(properties_dict),
+                        _ => None,
+                    },
+                ),
+                _ => (None, None, None),
+            };
+            visit_ops_in_object_readonly(
+                object_id,
+                document,
+                fonts,
+                xobjects,
+                properties,
+                current_actual_text,
+                debug_depth + (debug_depth > 0) as usize,
+                seen_ops,
+                visitor,
+                font_cache,
+            )?;
+        } else {
+            match operator.as_str() {
+                "BDC" | "BMC" => actual_text_stack.push(extract_actual_text(op, properties, document)),
+                // Never pop below index 0: that slot holds `initial_actual_text`, the ActualText
+                // (if any) inherited from whatever enclosing span invoked this object via `Do`,
+                // not a span opened in this object's own content stream — so a content stream
+                // with more EMCs than BDC/BMCs (a malformed/buggy producer) can't discard it.
+                "EMC" if actual_text_stack.len() > 1 => {
+                    actual_text_stack.pop();
+                }
+                _ => {}
+            }
+            let current_actual_text = actual_text_stack.iter().rev().find_map(|o| o.as_deref());
+            visitor.visit_op(
+                &mut content,
+                &mut i,
+                &|font_name: &str| {
+                    let font = fonts.unwrap().get(font_name.as_bytes()).unwrap();
+                    font.clone()
+                },
+                current_actual_text,
+            )
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Process `pages` of `document` concurrently (one thread per page), folding fonts/resources
+/// bookkeeping the same way `visit_page_content_stream_ops` does, but never touching content
+/// streams — so this is only for Phase 1 dumping, where `make_visitor` returns a fresh,
+/// independent visitor per page and the caller merges the returned per-page visitors' own
+/// accumulators (e.g. `TjFiles`) afterwards.
+pub fn visit_pages_parallel<V: OpVisitor + Send>(
+    document: &Document,
+    pages: &[u32],
+    debug: bool,
+    make_visitor: impl Fn() -> V + Sync,
+) -> anyhow::Result<Vec<V>> {
+    let all_pages = document.get_pages();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = pages
+            .iter()
+            .map(|&page_num| {
+                let all_pages = &all_pages;
+                let make_visitor = &make_visitor;
+                scope.spawn(move || -> anyhow::Result<V> {
+                    let page_id = *ok!(all_pages
+                        .get(&page_num)
+                        .ok_or_else(|| anyhow::anyhow!("no such page: {}", page_num)));
+                    let mut visitor = make_visitor();
+                    // A fresh cache per page/thread: still dedupes re-parsing a font that's
+                    // reused across this page's own XObjects, without needing to share a cache
+                    // (and thus a lock) across the threads `visit_pages_parallel` spawns.
+                    let mut font_cache = FontCache::new();
+                    let (resource_dict, resource_ids) = document.get_page_resources(page_id);
+                    let mut fonts = BTreeMap::new();
+                    if let Some(resources) = resource_dict {
+                        collect_fonts_from_resources(resources, &mut fonts, document, &mut font_cache);
+                    }
+                    for resource_id in &resource_ids {
+                        if let Ok(resources) = document.get_dictionary(*resource_id) {
+                            collect_fonts_from_resources(resources, &mut fonts, document, &mut font_cache);
+                        }
+                    }
+                    let mut xobjects = lopdf::Dictionary::new();
+                    if let Some(resource_dict) = resource_dict {
+                        if let Ok(lopdf::Object::Dictionary(ref dict)) =
+                            resource_dict.get(b"XObject")
+                        {
+                            xobjects.extend(dict);
+                        }
+                    }
+                    let mut properties = lopdf::Dictionary::new();
+                    if let Some(resource_dict) = resource_dict {
+                        if let Ok(lopdf::Object::Dictionary(ref dict)) =
+                            resource_dict.get(b"Properties")
+                        {
+                            properties.extend(dict);
+                        }
+                    }
+                    for resource_id in &resource_ids {
+                        if let Ok(resource_dict) = document.get_dictionary(*resource_id) {
+                            if let Ok(lopdf::Object::Dictionary(ref dict)) = resource_dict.get(b"XObject") {
+                                xobjects.extend(dict);
+                            }
+                            if let Ok(lopdf::Object::Dictionary(ref dict)) = resource_dict.get(b"Properties") {
+                                properties.extend(dict);
+                            }
+                        }
+                    }
+                    let mut seen_ops = linked_hash_map::LinkedHashMap::new();
+                    for object_id in document.get_page_contents(page_id) {
+                        visit_ops_in_object_readonly(
+                            object_id,
+                            document,
+                            Some(&fonts),
+                            Some(&xobjects),
+                            Some(&properties),
+                            None,
+                            debug as usize,
+                            &mut seen_ops,
+                            &mut visitor,
+                            &mut font_cache,
+                        )?;
+                    }
+                    Ok(visitor)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("a page-processing thread panicked"))
+            .collect()
+    })
+}
+
 /// For instance, given the dict for "15454 0", returns ("APZKLW+NotoSansDevanagari-Bold", "40531 0"), in this example:
 /// ...
 ///
@@ -416,8 +1369,6 @@ pub fn parse_font(referenced_font: &Dictionary, document: &Document) -> anyhow::
     let base_font_name = ok!(ok!(referenced_font.get(b"BaseFont")).as_name_str()).to_string();
     println!("Looking into referenced_font = {:#?}", referenced_font);
 
-    let encoding = referenced_font.get(b"Encoding")?.as_name_str()?.to_owned();
-
     fn get_subtype(referenced_font: &lopdf::Dictionary) -> FontSubtype {
         let subtype = referenced_font.get(b"Subtype");
         // println!("It has subtype: {:?}", subtype);
@@ -430,16 +1381,61 @@ pub fn parse_font(referenced_font: &Dictionary, document: &Document) -> anyhow::
     let font_subtype = get_subtype(referenced_font);
     let is_composite_font = matches!(font_subtype, FontSubtype::Type0);
     assert!(referenced_font.has(b"DescendantFonts") == is_composite_font);
+
+    // /Encoding is usually a predefined name (e.g. /Identity-H, /WinAnsiEncoding), but for a
+    // composite font it may instead be a reference to an embedded CMap stream with its own
+    // codespace ranges, which is what actually determines how many bytes make up one character
+    // code in this font's show-text operands.
+    let encoding_object = ok!(referenced_font.get(b"Encoding"));
+    let (encoding, code_width) = match encoding_object {
+        Object::Reference(id) => {
+            let stream = ok!(document.get_object(*id));
+            if stream.as_dict().is_ok() {
+                // A simple font's /Encoding can point at an encoding dictionary (/BaseEncoding +
+                // /Differences) rather than a bare name; still one byte per character code.
+                (format!("{:?}", id), if is_composite_font { 2 } else { 1 })
+            } else {
+                let width = code_width_from_cmap_stream(stream).unwrap_or(2);
+                (format!("{:?}", id), width)
+            }
+        }
+        Object::Dictionary(_) => ("<encoding dict>".to_string(), if is_composite_font { 2 } else { 1 }),
+        _ => {
+            let name = ok!(encoding_object.as_name_str()).to_owned();
+            // Identity-H/V and every other predefined composite-font CMap we've seen are 2-byte;
+            // simple fonts always use single-byte codes.
+            let width = if is_composite_font { 2 } else { 1 };
+            (name, width)
+        }
+    };
+
+    // A font dictionary's own `/ToUnicode` CMap (Table 111 in PDF32000_2008.pdf), if any. Present
+    // on both simple and composite fonts, so parse it once here rather than per-branch below.
+    let to_unicode = match referenced_font.get_deref(b"ToUnicode", document) {
+        Ok(stream_object) => ToUnicodeCMap::parse(stream_object).ok(),
+        Err(_) => None,
+    };
+
     // Simple font.
     if !is_composite_font {
+        let simple_font_encoding_table = Some(resolve_simple_font_encoding(encoding_object, document));
+        let font_program = load_font_program(referenced_font, document);
+        let average_glyph_width = average_simple_glyph_width(referenced_font, document);
         return Ok(Font {
+            font_id: None,
             base_font_name: Some(base_font_name),
             font_descriptor_id: Some(ok!(
                 ok!(referenced_font.get(b"FontDescriptor")).as_reference()
             )),
             encoding: Some(encoding),
+            code_width,
             subtype: Some(font_subtype),
-            to_unicode: None,
+            to_unicode,
+            simple_font_encoding_table,
+            type0_encoding: None,
+            cid_to_gid_map: None,
+            font_program,
+            average_glyph_width,
             font_descriptor: None,
         });
     }
@@ -478,14 +1474,26 @@ pub fn parse_font(referenced_font: &Dictionary, document: &Document) -> anyhow::
         }
     }
 
+    let type0_encoding = Some(resolve_type0_encoding(encoding_object, document));
+    let cid_to_gid_map = Some(CidToGidMap::parse(descendant_font, document));
+    let font_program = load_font_program(descendant_font, document);
+    let average_glyph_width = average_composite_glyph_width(descendant_font, document);
+
     Ok(Font {
+        font_id: None,
         base_font_name: Some(base_font_name),
         font_descriptor_id: Some(ok!(
             ok!(descendant_font.get(b"FontDescriptor")).as_reference()
         )),
         encoding: Some(encoding),
+        code_width,
         subtype: Some(font_subtype),
-        to_unicode: None,
+        to_unicode,
+        simple_font_encoding_table: None,
+        type0_encoding,
+        cid_to_gid_map,
+        font_program,
+        average_glyph_width,
         font_descriptor: None,
     })
 }
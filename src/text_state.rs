@@ -1,16 +1,64 @@
-use std::{collections::HashMap, fs::File, io::Write};
+use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 use lopdf::ObjectId;
-use serde_derive::Deserialize;
 
-use crate::{ok, pdf_visit::ToUnicodeCMap};
-use crate::{pdf_visit, Phase};
+use crate::{cmap_writer, font_mapping, ok, pdf_visit::ToUnicodeCMap};
+use crate::{pdf_visit, FixMode, Phase};
+
+/// Which layer of the mapping chain (see `TextState::load_font_map`) supplied a glyph's text,
+/// recorded alongside it purely for auditing (e.g. in the TOML `dump_font_glyph_mappings` writes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingSource {
+    /// A hand-edited TOML mapping file in `maps_dir` (highest priority of the sources loaded up
+    /// front; only an `ActualText`-recovered mapping overrides it).
+    Toml,
+    /// The font dictionary's own embedded `/ToUnicode` CMap.
+    ToUnicode,
+    /// A simple font's base encoding (+ `/Differences`) resolved through the Adobe Glyph List.
+    SimpleEncoding,
+    /// The embedded font program's own cmap/post (or CFF charset), read directly off
+    /// `pdf_visit::Font::font_program`. Weakest of the automatic sources (a heuristic, not
+    /// something the PDF itself declares as a glyph->text mapping), so it's inserted first and
+    /// anything the stronger sources above cover overwrites it.
+    FontProgram,
+    /// Typed in at the interactive prompt (lowest priority: only when nothing else had it).
+    Prompt,
+    /// A `/Span <</ActualText ...>> BDC ... EMC` the source PDF itself supplied, recovered for a
+    /// single-glyph run (highest priority: an author's own replacement text for this exact
+    /// occurrence beats every other source, including hand-edited TOML).
+    ActualText,
+}
+impl MappingSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MappingSource::Toml => "toml",
+            MappingSource::ToUnicode => "tounicode",
+            MappingSource::SimpleEncoding => "simple_encoding",
+            MappingSource::FontProgram => "font_program",
+            MappingSource::Prompt => "prompt",
+            MappingSource::ActualText => "actual_text",
+        }
+    }
+}
+
+/// One glyph's resolved replacement text, plus which layer of the mapping chain supplied it.
+#[derive(Debug, Clone)]
+pub struct GlyphMapping {
+    pub text: String,
+    pub source: MappingSource,
+}
 
 pub struct TextState {
     pub current_font: pdf_visit::Font,
     // Hack: Keeping track of the current Tm matrix, just its third component will do for now.
     pub current_tm_c: f64,
+    /// Analogous to Ghostscript's `-dIgnoreToUnicode`: drop the embedded `/ToUnicode` layer
+    /// entirely, for PDFs whose ToUnicode CMap is syntactically valid but semantically wrong.
+    pub ignore_tounicode: bool,
+    /// See `TextState::glyph_ids_with_space_hints`: how big a `TJ` gap (as a multiple of the
+    /// current font's average glyph width) counts as a reconstructed word break.
+    pub space_threshold: f64,
 }
 
 impl TextState {
@@ -35,13 +83,23 @@ impl TextState {
         };
     }
 
-    /// For the PDF text-showing operators (Tj ' " TJ), convert the operand into a vector (the glyph ids in the font).
-    /// TODO: This assumes glyph ids are 16-bit, which is true for "composite" fonts that have a CMAP,
-    /// but for "simple" fonts, glyph ids are just 8-bit. See 9.4.3 (p. 251) of PDF32000_2008.pdf.
-    fn glyph_ids(
-        op: &lopdf::content::Operation,
-        font_subtype: &pdf_visit::FontSubtype,
-    ) -> Vec<u16> {
+    /// Decode one code-width-delimited run of bytes into glyph ids: for simple fonts, one
+    /// `code_width`-byte (1) code per glyph; for composite (Type0) fonts, the proper two-stage
+    /// decode through `font`'s `/Encoding` CMap and `/CIDToGIDMap` (see
+    /// `pdf_visit::glyph_ids_for_composite_font`).
+    fn decode_codes(bytes: &[u8], font: &pdf_visit::Font) -> Vec<u16> {
+        match font.subtype {
+            Some(pdf_visit::FontSubtype::Type0) => pdf_visit::glyph_ids_for_composite_font(bytes, font),
+            _ => bytes
+                .chunks(font.code_width)
+                .map(|chunk| chunk.iter().fold(0u16, |acc, &byte| (acc << 8) | byte as u16))
+                .collect(),
+        }
+    }
+
+    /// For the PDF text-showing operators (Tj ' " TJ), convert the operand into a vector of glyph
+    /// ids (see `decode_codes`). See 9.4.3 (p. 251) of PDF32000_2008.pdf.
+    fn glyph_ids(op: &lopdf::content::Operation, font: &pdf_visit::Font) -> Vec<u16> {
         let operator = &op.operator;
         let mut bytes: Vec<u8> = Vec::new();
         let text: &[u8] = match operator.as_str() {
@@ -73,162 +131,315 @@ impl TextState {
             }
             _ => unreachable!(),
         };
-        match font_subtype {
-            pdf_visit::FontSubtype::Type0 => {
-                text.chunks(2).map(|chunk| from_two_bytes(chunk)).collect()
-            }
-            pdf_visit::FontSubtype::Type1
-            | pdf_visit::FontSubtype::MMType1
-            | pdf_visit::FontSubtype::Type3
-            | pdf_visit::FontSubtype::TrueType => {
-                text.chunks(1).map(|chunk| chunk[0] as u16).collect()
+        Self::decode_codes(text, font)
+    }
+
+    /// Like `glyph_ids`, but for `TJ` also returns the set of output-glyph indices before which a
+    /// space should be inferred. A `TJ` operand interleaves string segments with numeric
+    /// positioning adjustments; each adjustment is in the same /1000 glyph-space units as
+    /// `font.average_glyph_width` (see `pdf_visit::Font::average_glyph_width`), so the two are
+    /// directly comparable without knowing the font size. A positive adjustment moves the next
+    /// glyph closer (tighter kerning); a negative one moves it further away, and when that gap is
+    /// at least `space_threshold` times the font's average glyph width, we treat it as a
+    /// reconstructed word break rather than ordinary kerning. `Tj`/`'`/`"` never carry positioning
+    /// adjustments, so they always return an empty set.
+    fn glyph_ids_with_space_hints(
+        op: &lopdf::content::Operation,
+        font: &pdf_visit::Font,
+        space_threshold: f64,
+    ) -> (Vec<u16>, HashSet<usize>) {
+        if op.operator != "TJ" {
+            return (Self::glyph_ids(op, font), HashSet::new());
+        }
+        assert_eq!(op.operands.len(), 1);
+        let average_width = font.average_glyph_width;
+        let mut ids = Vec::new();
+        let mut spaces = HashSet::new();
+        let mut pending_adjustment = 0.0;
+        for element in op.operands[0].as_array().unwrap() {
+            match element {
+                lopdf::Object::String(s, _) => {
+                    if let Some(average_width) = average_width {
+                        if average_width > 0.0 && -pending_adjustment >= space_threshold * average_width {
+                            spaces.insert(ids.len());
+                        }
+                    }
+                    pending_adjustment = 0.0;
+                    ids.extend(Self::decode_codes(s, font));
+                }
+                lopdf::Object::Real(n) => pending_adjustment += *n as f64,
+                lopdf::Object::Integer(n) => pending_adjustment += *n as f64,
+                _ => assert!(false, "Unexpected per PDF spec: {:#?}", element),
             }
         }
+        (ids, spaces)
     }
 
-    fn visit_text_showing_operator_dump(
-        &self,
-        glyph_ids: &[u16],
-        maps_dir: &std::path::PathBuf,
-        files: &mut TjFiles,
-    ) {
-        let glyph_hexes: Vec<String> = glyph_ids.iter().map(|n| format!("{:04X} ", n)).collect();
-        let file = {
-            files
-                .file
-                .entry(self.current_font.font_descriptor_id.unwrap())
-                .or_insert_with(|| {
-                    let filename = std::path::Path::new(maps_dir).join(
-                        basename_for_font(
-                            self.current_font.font_descriptor_id.unwrap(),
-                            &self.current_font.base_font_name.as_ref().unwrap(),
-                        ) + ".Tjs",
-                    );
-                    println!("Creating file: {:?}", filename);
-                    std::fs::create_dir_all(maps_dir.clone()).unwrap();
-                    std::fs::File::create(filename).unwrap()
-                })
-        };
-        glyph_hexes
-            .iter()
-            .for_each(|g| file.write_all(g.as_bytes()).unwrap());
-        file.write_all(b"\n").unwrap();
+    fn visit_text_showing_operator_dump(&self, glyph_ids: &[u16], files: &mut TjFiles) {
+        let glyph_hexes: String = glyph_ids.iter().map(|n| format!("{:04X} ", n)).collect();
+        let (_, buffer) = files
+            .buffer
+            .entry(self.current_font.font_descriptor_id.unwrap())
+            .or_insert_with(|| {
+                (
+                    self.current_font.base_font_name.clone().unwrap(),
+                    String::new(),
+                )
+            });
+        buffer.push_str(&glyph_hexes);
+        buffer.push('\n');
     }
 
-    fn visit_text_showing_operator_wrap(
+    /// Load (if not already cached in `font_glyph_mappings`) the current font's glyph mapping and
+    /// return a mutable reference to its entry, resolving each glyph through an ordered chain of
+    /// sources (lowest to highest priority, each overwriting the last where they overlap): the
+    /// embedded font program's own cmap/post or CFF charset (see
+    /// `pdf_visit::Font::font_program`), a simple font's base-encoding + `/Differences` table (see
+    /// `pdf_visit::Font::simple_font_encoding_table`), the font dictionary's embedded
+    /// `/ToUnicode` CMap (which `--ignore-tounicode` drops entirely), and finally hand-edited
+    /// TOML. This mirrors Ghostscript's use of ToUnicode/encoding to recover character values, so
+    /// a well-formed font needs little or no hand-edited TOML to begin with.
+    fn load_font_map<'a>(
         &self,
-        glyph_ids: &[u16],
         maps_dir: &std::path::PathBuf,
-        font_glyph_mappings: &mut HashMap<lopdf::ObjectId, HashMap<u16, String>>,
-    ) -> lopdf::Dictionary {
-        // Phase 2: Wrap the operator in /ActualText.
-
-        // Before: content[i] = op.
-        // After:
-        //         content[i] = BDC [/Span <</ActualText (...)>>]
-        //         content[i + 1] = op
-        //         content[i + 2] = EMC []
-        //         i = i + 2
+        font_glyph_mappings: &'a mut HashMap<lopdf::ObjectId, HashMap<u16, GlyphMapping>>,
+    ) -> &'a mut HashMap<u16, GlyphMapping> {
         let current_font = &self.current_font;
+        if !font_glyph_mappings.contains_key(&current_font.font_descriptor_id.unwrap()) {
+            let mut font_glyph_mapping = HashMap::<u16, GlyphMapping>::new();
 
-        // The string that be encoded into /ActualText surrounding those glyphs.
-        let mytext = {
-            // println!("Looking up font {:?}", current_font);
-            if !font_glyph_mappings.contains_key(&current_font.font_descriptor_id.unwrap()) {
-                let font_glyph_mapping = {
-                    let base_font_name = &current_font.base_font_name.as_ref().unwrap();
-                    let font_id = current_font.font_descriptor_id.unwrap();
-                    let glob_pattern =
-                        format!("{}/*{}.toml", maps_dir.to_string_lossy(), base_font_name);
-                    println!(
-                        "For font {:?} = {}, looking for map files matching pattern #{}#",
-                        font_id, base_font_name, glob_pattern
+            if let Some(font_program) = &current_font.font_program {
+                for (&glyph_id, text) in font_program {
+                    font_glyph_mapping.insert(
+                        glyph_id,
+                        GlyphMapping {
+                            text: text.clone(),
+                            source: MappingSource::FontProgram,
+                        },
                     );
-                    let mut filename = std::path::PathBuf::new();
-                    for entry in glob::glob(&glob_pattern).expect("Failed to read glob pattern") {
-                        match entry {
-                            Ok(path) => filename = path,
-                            Err(e) => {
-                                println!("While trying to match {}: {:?}", glob_pattern, e)
-                            }
-                        }
-                    }
-                    println!("Trying to read from filename {:?}", filename);
-
-                    #[derive(Deserialize)]
-                    struct Replacements {
-                        replacement_text: String,
-                        #[serde(rename = "replacement_codes")]
-                        _replacement_codes: Vec<i32>,
-                        #[serde(rename = "replacement_desc")]
-                        _replacement_desc: Vec<String>,
-                    }
+                }
+            }
 
-                    let m: HashMap<String, Replacements> =
-                        toml::from_slice(&std::fs::read(filename).unwrap()).unwrap();
-                    let mut ret = HashMap::<u16, String>::new();
-                    for (glyph_id_str, replacements) in m {
-                        ret.insert(
-                            u16::from_str_radix(&glyph_id_str, 16).unwrap(),
-                            replacements.replacement_text,
+            if let Some(table) = &current_font.simple_font_encoding_table {
+                for (code, text) in table.iter().enumerate() {
+                    if let Some(text) = text {
+                        font_glyph_mapping.insert(
+                            code as u16,
+                            GlyphMapping {
+                                text: text.clone(),
+                                source: MappingSource::SimpleEncoding,
+                            },
                         );
                     }
-                    ret
-                };
+                }
+            }
 
-                font_glyph_mappings
-                    .insert(current_font.font_descriptor_id.unwrap(), font_glyph_mapping);
+            if !self.ignore_tounicode {
+                for (glyph_id, text) in current_font.to_unicode_entries() {
+                    font_glyph_mapping.insert(
+                        glyph_id,
+                        GlyphMapping {
+                            text: text.to_string(),
+                            source: MappingSource::ToUnicode,
+                        },
+                    );
+                }
             }
-            let current_map = font_glyph_mappings
-                .get_mut(&current_font.font_descriptor_id.unwrap())
-                .unwrap();
 
-            let actual_text_string = glyph_ids
-                .iter()
-                .map(|glyph_id| {
-                    if let Some(v) = current_map.get(glyph_id) {
-                        v.to_string()
-                    } else {
-                        println!(
-                            "No mapping found for glyph {:04X} in font {}!",
-                            glyph_id,
-                            current_font.base_font_name.as_ref().unwrap()
-                        );
-                        println!("Nevermind, enter replacement text now:");
-                        let replacement: String = text_io::read!("{}\n"); // Quiet alternative: format!("[glyph{:04X}]", glyph_id);
-                        println!("Thanks, using replacement #{}#", replacement);
-                        current_map.insert(*glyph_id, replacement.clone());
-                        replacement
-                    }
-                })
-                .join("");
-            // Hack: Surround the ActualText with the font name. Better would be to do this in the equivalent of `pdftotext`.
-            let actual_text_string = format!(
-                "[{}]{}[/{}]",
-                current_font.base_font_name.as_ref().unwrap(),
-                actual_text_string,
-                current_font.base_font_name.as_ref().unwrap()
+            let base_font_name = &current_font.base_font_name.as_ref().unwrap();
+            let font_id = current_font.font_descriptor_id.unwrap();
+            let glob_pattern =
+                format!("{}/*{}.toml", maps_dir.to_string_lossy(), base_font_name);
+            println!(
+                "For font {:?} = {}, looking for map files matching pattern #{}#",
+                font_id, base_font_name, glob_pattern
             );
-            if self.current_tm_c > 0.0 {
-                "[sl]".to_owned() + &actual_text_string + "[/sl]"
+            let mut filename = None;
+            for entry in glob::glob(&glob_pattern).expect("Failed to read glob pattern") {
+                match entry {
+                    Ok(path) => filename = Some(path),
+                    Err(e) => {
+                        println!("While trying to match {}: {:?}", glob_pattern, e)
+                    }
+                }
+            }
+            if let Some(filename) = filename {
+                println!("Trying to read from filename {:?}", filename);
+
+                match font_mapping::FontMapping::load_from_path(&filename) {
+                    Ok(mapping) => {
+                        for (glyph_id, replacement) in mapping.iter() {
+                            font_glyph_mapping.insert(
+                                glyph_id,
+                                GlyphMapping {
+                                    text: replacement.replacement_text.clone(),
+                                    source: MappingSource::Toml,
+                                },
+                            );
+                        }
+                    }
+                    Err(e) => println!("While trying to read {:?}: {:?}", filename, e),
+                }
             } else {
-                actual_text_string
+                println!(
+                    "No TOML map file found for font {:?}; relying on ToUnicode seeding (if any) and interactive prompts.",
+                    font_id
+                );
             }
-            // let re1 = regex::Regex::new(r"ि<CCsucc>(([क-ह]्)*[क-ह])").unwrap();
-            // let actual_text_string = re1.replace_all(&actual_text_string, r"\1ि");
-            // let re2 = regex::Regex::new(r"(([क-ह]्)*[क-ह][^क-ह]*)र्<CCprec>").unwrap();
-            // let actual_text_string = re2.replace_all(&actual_text_string, r"र्\1");
-            // // if actual_text_string.contains("<CC") {
-            // //     println!("Some leftovers in #{}#", actual_text_string);
-            // // }
-            // return Ok(actual_text_string.to_string());
-        };
 
-        let dict = lopdf::dictionary!(
+            font_glyph_mappings
+                .insert(current_font.font_descriptor_id.unwrap(), font_glyph_mapping);
+        }
+        font_glyph_mappings
+            .get_mut(&current_font.font_descriptor_id.unwrap())
+            .unwrap()
+    }
+
+    /// Resolve each glyph id to its mapped text, consulting (and lazily populating, from the
+    /// TOML files in `maps_dir`, or by prompting) the per-font entry of `font_glyph_mappings`.
+    /// Shared by both Phase-2 output modes (`/ActualText` wrapping and `/ToUnicode` generation),
+    /// since both ultimately need the same glyph→text resolution.
+    fn resolve_glyph_texts(
+        &self,
+        glyph_ids: &[u16],
+        maps_dir: &std::path::PathBuf,
+        font_glyph_mappings: &mut HashMap<lopdf::ObjectId, HashMap<u16, GlyphMapping>>,
+        actual_text_override: Option<&str>,
+    ) -> Vec<String> {
+        let current_font = &self.current_font;
+        // Still populate `font_glyph_mappings` for this font even when we're about to return the
+        // ActualText override below: `attach_tounicode_cmaps` (FixMode::ToUnicode) only emits a
+        // `/ToUnicode` CMap for fonts that have an entry here, and a font used exclusively inside
+        // ActualText-wrapped runs would otherwise never get one.
+        let current_map = self.load_font_map(maps_dir, font_glyph_mappings);
+        // Step 4 of the §9.10 fallback chain (see the doc comment on `pdf_visit::Font`): this run
+        // sits inside a `/Span <</ActualText ...>> BDC ... EMC` the source PDF itself supplied, so
+        // trust it verbatim rather than decoding glyph-by-glyph — the author's replacement text is
+        // exactly what the earlier steps are only trying to approximate.
+        if let Some(text) = actual_text_override {
+            // A single-glyph run's ActualText unambiguously tells us what that one glyph id
+            // means, so record it like any other source — unlike a multi-glyph run, where the
+            // override text can't be split back up per glyph. This is what lets the glyph still
+            // resolve (without hitting the interactive prompt below) the next time it shows up
+            // outside an ActualText span, and what lets FixMode::ToUnicode's CMap include it.
+            if let [glyph_id] = glyph_ids {
+                current_map.insert(
+                    *glyph_id,
+                    GlyphMapping { text: text.to_string(), source: MappingSource::ActualText },
+                );
+            }
+            return vec![text.to_string()];
+        }
+        glyph_ids
+            .iter()
+            .map(|glyph_id| {
+                if let Some(v) = current_map.get(glyph_id) {
+                    v.text.clone()
+                } else {
+                    println!(
+                        "No mapping found for glyph {:04X} in font {}!",
+                        glyph_id,
+                        current_font.base_font_name.as_ref().unwrap()
+                    );
+                    println!("Nevermind, enter replacement text now:");
+                    let replacement: String = text_io::read!("{}\n"); // Quiet alternative: format!("[glyph{:04X}]", glyph_id);
+                    println!("Thanks, using replacement #{}#", replacement);
+                    current_map.insert(
+                        *glyph_id,
+                        GlyphMapping {
+                            text: replacement.clone(),
+                            source: MappingSource::Prompt,
+                        },
+                    );
+                    replacement
+                }
+            })
+            .collect()
+    }
+
+    /// For the diagnostic highlight mode: what fraction of `glyph_ids` have a known mapping
+    /// (from any source `load_font_map` seeds — this never prompts, since highlighting is meant to run unattended as a QA
+    /// pass before any real mapping work happens).
+    fn mapped_fraction(
+        &self,
+        glyph_ids: &[u16],
+        maps_dir: &std::path::PathBuf,
+        font_glyph_mappings: &mut HashMap<lopdf::ObjectId, HashMap<u16, GlyphMapping>>,
+    ) -> f64 {
+        if glyph_ids.is_empty() {
+            return 1.0;
+        }
+        let current_map = self.load_font_map(maps_dir, font_glyph_mappings);
+        let mapped = glyph_ids
+            .iter()
+            .filter(|glyph_id| current_map.contains_key(glyph_id))
+            .count();
+        mapped as f64 / glyph_ids.len() as f64
+    }
+
+    /// Phase 2 ("actual-text" mode): build the `/ActualText` property dictionary for the operator.
+    /// The caller (`MyOpVisitor::visit_text_showing_operator`) does the wrapping:
+    ///
+    /// Before: content[i] = op.
+    /// After:
+    ///         content[i] = BDC [/Span <</ActualText (...)>>]
+    ///         content[i + 1] = op
+    ///         content[i + 2] = EMC []
+    ///         i = i + 2
+    fn visit_text_showing_operator_wrap(
+        &self,
+        glyph_ids: &[u16],
+        space_hints: &HashSet<usize>,
+        maps_dir: &std::path::PathBuf,
+        font_glyph_mappings: &mut HashMap<lopdf::ObjectId, HashMap<u16, GlyphMapping>>,
+        actual_text_override: Option<&str>,
+    ) -> lopdf::Dictionary {
+        // The ActualText payload is exactly the decoded Unicode for this run (plus any word breaks
+        // `space_hints` reconstructed from the TJ operand's positioning adjustments): no extra
+        // markup. (An earlier version of this surrounded it with "[FontName]...[/FontName]" and
+        // "[sl]" bracket hacks for a downstream post-processing step; that's not part of the PDF
+        // spec, and broke any consumer — screen reader, copy/paste, search index — that takes
+        // /ActualText at face value. See 14.9.4 "Replacement Text" in PDF32000_2008.pdf.)
+        let mut actual_text_string = String::new();
+        for (i, text) in self
+            .resolve_glyph_texts(glyph_ids, maps_dir, font_glyph_mappings, actual_text_override)
+            .iter()
+            .enumerate()
+        {
+            if space_hints.contains(&i) {
+                actual_text_string.push(' ');
+            }
+            actual_text_string.push_str(text);
+        }
+
+        lopdf::dictionary!(
         "ActualText" => lopdf::Object::String(
-            pdf_encode_unicode_text_string(&mytext),
-            lopdf::StringFormat::Hexadecimal));
-        dict
+            pdf_encode_unicode_text_string(&actual_text_string),
+            lopdf::StringFormat::Hexadecimal))
+    }
+
+    /// Diagnostic mode: if this run's mapped-glyph fraction is below `threshold`, recolor it by
+    /// bracketing the show operator with `q` + `r g b rg` (save graphics state, set nonstroking
+    /// color) before it, and `Q` (restore graphics state) after. Returns `None` for runs at or
+    /// above the threshold (e.g. fully-mapped runs under the default threshold), which are left
+    /// alone and so stay whatever color the page already draws them in.
+    fn visit_text_showing_operator_highlight(
+        &self,
+        glyph_ids: &[u16],
+        maps_dir: &std::path::PathBuf,
+        font_glyph_mappings: &mut HashMap<lopdf::ObjectId, HashMap<u16, GlyphMapping>>,
+        threshold: f64,
+        color: (f64, f64, f64),
+    ) -> Option<Vec<lopdf::content::Operation>> {
+        let fraction = self.mapped_fraction(glyph_ids, maps_dir, font_glyph_mappings);
+        if fraction >= threshold {
+            return None;
+        }
+        let (r, g, b) = color;
+        Some(vec![
+            lopdf::content::Operation::new("q", vec![]),
+            lopdf::content::Operation::new("rg", vec![r.into(), g.into(), b.into()]),
+        ])
     }
 }
 
@@ -236,8 +447,19 @@ pub struct MyOpVisitor {
     pub text_state: TextState,
     pub maps_dir: std::path::PathBuf,
     pub files: TjFiles,
-    pub font_glyph_mappings: HashMap<ObjectId, HashMap<u16, String>>,
+    pub font_glyph_mappings: HashMap<ObjectId, HashMap<u16, GlyphMapping>>,
     pub phase: Phase,
+    /// Which Phase-2 output to produce: marked-content /ActualText wrapping, a /ToUnicode CMap, or
+    /// highlighting of runs with unmapped glyphs.
+    pub fix_mode: FixMode,
+    /// font_descriptor_id -> font_id (the font dictionary's own object id), so that a /ToUnicode
+    /// CMap built from `font_glyph_mappings` (keyed by descriptor id) can be attached to the right
+    /// font dictionary.
+    pub font_ids: HashMap<ObjectId, ObjectId>,
+    /// FixMode::Highlight only: recolor a run when its mapped-glyph fraction is below this.
+    pub highlight_threshold: f64,
+    /// FixMode::Highlight only: the nonstroking color (r, g, b) to recolor flagged runs with.
+    pub highlight_color: (f64, f64, f64),
 }
 
 impl MyOpVisitor {
@@ -246,50 +468,128 @@ impl MyOpVisitor {
         op: &lopdf::content::Operation,
         content: &mut lopdf::content::Content,
         i: &mut usize,
+        current_actual_text: Option<&str>,
     ) {
-        // First get the list of glyph_ids for this operator.
-        let glyph_ids: Vec<u16> =
-            TextState::glyph_ids(op, self.text_state.current_font.subtype.as_ref().unwrap());
+        // First get the list of glyph_ids for this operator (and, for `FixMode::ActualText`, the
+        // word breaks reconstructed from a TJ operand's positioning adjustments).
+        let (glyph_ids, space_hints): (Vec<u16>, HashSet<usize>) =
+            TextState::glyph_ids_with_space_hints(
+                op,
+                &self.text_state.current_font,
+                self.text_state.space_threshold,
+            );
 
         match self.phase {
-            // Phase 1: Write to file.
-            Phase::Phase1Dump => self.text_state.visit_text_showing_operator_dump(
-                &glyph_ids,
-                &self.maps_dir,
-                &mut self.files,
-            ),
-            Phase::Phase2Fix => {
-                let dict = self.text_state.visit_text_showing_operator_wrap(
-                    &glyph_ids,
-                    &self.maps_dir,
-                    &mut self.font_glyph_mappings,
-                );
-                content.operations.insert(
-                    *i,
-                    lopdf::content::Operation::new(
-                        "BDC",
-                        vec![lopdf::Object::from("Span"), lopdf::Object::Dictionary(dict)],
-                    ),
-                );
-                content
-                    .operations
-                    .insert(*i + 2, lopdf::content::Operation::new("EMC", vec![]));
-                *i = *i + 2;
-            }
+            // Phase 1: Accumulate into `self.files`, flushed to disk once all pages are visited.
+            Phase::Phase1Dump => self
+                .text_state
+                .visit_text_showing_operator_dump(&glyph_ids, &mut self.files),
+            Phase::Phase2Fix => match self.fix_mode {
+                // If this run already sits inside a source-PDF `/Span <</ActualText ...>> BDC ...
+                // EMC`, it's already unambiguous to every consumer; wrapping it again in an
+                // identical nested span would just bloat the content stream with redundant markup.
+                FixMode::ActualText if current_actual_text.is_some() => {}
+                FixMode::ActualText => {
+                    let dict = self.text_state.visit_text_showing_operator_wrap(
+                        &glyph_ids,
+                        &space_hints,
+                        &self.maps_dir,
+                        &mut self.font_glyph_mappings,
+                        current_actual_text,
+                    );
+                    content.operations.insert(
+                        *i,
+                        lopdf::content::Operation::new(
+                            "BDC",
+                            vec![lopdf::Object::from("Span"), lopdf::Object::Dictionary(dict)],
+                        ),
+                    );
+                    content
+                        .operations
+                        .insert(*i + 2, lopdf::content::Operation::new("EMC", vec![]));
+                    *i = *i + 2;
+                }
+                // Don't touch the content stream at all: just resolve (and accumulate) the glyph
+                // mapping for this font, so a /ToUnicode CMap can be attached once, after all
+                // pages are visited. See `MyOpVisitor::attach_tounicode_cmaps`.
+                FixMode::ToUnicode => {
+                    self.text_state.resolve_glyph_texts(
+                        &glyph_ids,
+                        &self.maps_dir,
+                        &mut self.font_glyph_mappings,
+                        current_actual_text,
+                    );
+                }
+                FixMode::Highlight => {
+                    if let Some(before_ops) = self.text_state.visit_text_showing_operator_highlight(
+                        &glyph_ids,
+                        &self.maps_dir,
+                        &mut self.font_glyph_mappings,
+                        self.highlight_threshold,
+                        self.highlight_color,
+                    ) {
+                        let n = before_ops.len();
+                        for (offset, op) in before_ops.into_iter().enumerate() {
+                            content.operations.insert(*i + offset, op);
+                        }
+                        content
+                            .operations
+                            .insert(*i + n + 1, lopdf::content::Operation::new("Q", vec![]));
+                        *i += n + 1;
+                    }
+                }
+            },
         };
     }
 
     pub fn dump_font_glyph_mappings(&self) {
+        #[derive(serde_derive::Serialize)]
+        struct DumpedMapping {
+            text: String,
+            // Which layer of the mapping chain supplied `text` (see `MappingSource`), so a
+            // reviewer auditing the TOML can tell a ToUnicode-seeded guess from a vetted one.
+            source: String,
+        }
         for (k, v) in &self.font_glyph_mappings {
             let map_filename = format!("map-{}-{}.toml", k.0, k.1);
             println!("Creating file: {:?}", map_filename);
-            let mut map_for_toml: HashMap<String, String> = HashMap::new();
-            for (glyph_id, text) in v {
-                map_for_toml.insert(format!("{:04X}", glyph_id), text.to_string());
+            let mut map_for_toml: HashMap<String, DumpedMapping> = HashMap::new();
+            for (glyph_id, mapping) in v {
+                map_for_toml.insert(
+                    format!("{:04X}", glyph_id),
+                    DumpedMapping {
+                        text: mapping.text.clone(),
+                        source: mapping.source.as_str().to_string(),
+                    },
+                );
             }
             let _ = std::fs::write(map_filename, toml::to_vec(&map_for_toml).unwrap());
         }
     }
+
+    /// For `FixMode::ToUnicode`: build a `/ToUnicode` CMap stream out of each font's collected
+    /// glyph mapping, and attach it to that font's dictionary.
+    pub fn attach_tounicode_cmaps(&self, document: &mut lopdf::Document) -> anyhow::Result<()> {
+        for (font_descriptor_id, mapping) in &self.font_glyph_mappings {
+            let font_id = match self.font_ids.get(font_descriptor_id) {
+                Some(id) => *id,
+                None => {
+                    println!(
+                        "No font dictionary id recorded for descriptor {:?}; skipping /ToUnicode.",
+                        font_descriptor_id
+                    );
+                    continue;
+                }
+            };
+            let texts: HashMap<u16, String> = mapping
+                .iter()
+                .map(|(&glyph_id, m)| (glyph_id, m.text.clone()))
+                .collect();
+            let cmap_bytes = cmap_writer::build_tounicode_cmap(&texts);
+            cmap_writer::attach_tounicode_cmap(document, font_id, cmap_bytes)?;
+        }
+        Ok(())
+    }
 }
 
 impl pdf_visit::OpVisitor for MyOpVisitor {
@@ -298,16 +598,25 @@ impl pdf_visit::OpVisitor for MyOpVisitor {
         content: &mut lopdf::content::Content,
         i: &mut usize,
         get_font_from_name: &dyn Fn(&str) -> pdf_visit::Font,
+        current_actual_text: Option<&str>,
     ) {
         let op = content.operations[*i].clone();
         match op.operator.as_str() {
             // Setting a new font.
-            "Tf" => self.text_state.visit_Tf(&op, get_font_from_name),
+            "Tf" => {
+                self.text_state.visit_Tf(&op, get_font_from_name);
+                let current_font = &self.text_state.current_font;
+                if let (Some(descriptor_id), Some(font_id)) =
+                    (current_font.font_descriptor_id, current_font.font_id)
+                {
+                    self.font_ids.insert(descriptor_id, font_id);
+                }
+            }
             // Setting font matrix.
             "Tm" => self.text_state.visit_Tm(&op),
             // An actual text-showing operator.
             "Tj" | "TJ" | "'" | "\"" => {
-                self.visit_text_showing_operator(&op, content, i);
+                self.visit_text_showing_operator(&op, content, i, current_actual_text);
             }
             // None of the cases we care about.
             _ => {
@@ -320,17 +629,42 @@ impl pdf_visit::OpVisitor for MyOpVisitor {
     }
 }
 
-pub fn from_two_bytes(bytes: &[u8]) -> u16 {
-    assert_eq!(bytes.len(), 2);
-    (bytes[0] as u16) * 256 + (bytes[1] as u16)
-}
-
 /// Used for dumping both Tj operands, and unicode mappings ("CMap"s).
 fn basename_for_font(font_id: ObjectId, base_font_name: &str) -> String {
     format!("font-{}-{}-{}", font_id.0, font_id.1, base_font_name)
 }
+/// Accumulates the Phase-1 "Tjs" dump in memory, per font (keyed by font descriptor id, paired
+/// with its base font name for the eventual filename), so pages can be visited in parallel (see
+/// `pdf_visit::visit_pages_parallel`) and each page's/thread's `TjFiles` merged before the one
+/// `.Tjs` file per font is written out.
+#[derive(Default)]
 pub struct TjFiles {
-    pub file: HashMap<lopdf::ObjectId, File>,
+    pub buffer: HashMap<lopdf::ObjectId, (String, String)>,
+}
+
+impl TjFiles {
+    /// Fold `other`'s per-font buffers into `self`, appending where both have the same font.
+    pub fn merge(&mut self, other: TjFiles) {
+        for (font_descriptor_id, (base_font_name, text)) in other.buffer {
+            self.buffer
+                .entry(font_descriptor_id)
+                .or_insert_with(|| (base_font_name, String::new()))
+                .1
+                .push_str(&text);
+        }
+    }
+
+    /// Write one `.Tjs` file per font into `maps_dir`, now that every page has been visited.
+    pub fn flush(&self, maps_dir: &std::path::Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(maps_dir)?;
+        for (font_descriptor_id, (base_font_name, text)) in &self.buffer {
+            let filename =
+                maps_dir.join(basename_for_font(*font_descriptor_id, base_font_name) + ".Tjs");
+            println!("Creating file: {:?}", filename);
+            std::fs::write(filename, text)?;
+        }
+        Ok(())
+    }
 }
 
 /// The PDF format expects a particular encoding for Unicode strings:  